@@ -0,0 +1,252 @@
+//! Playback of animated GIF, APNG and WebP key images on Elgato devices, which can only display a single frame at a time, and flattening of them for other devices.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::Cursor;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+
+use base64::Engine as _;
+use image::{AnimationDecoder, DynamicImage, ImageFormat};
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+/// The shortest time that a frame is displayed for, capping the frame rate of all animations.
+const MIN_FRAME_INTERVAL: Duration = Duration::from_millis(1000 / 30);
+/// The delay used for frames that do not specify one, matching the behaviour of web browsers.
+const DEFAULT_FRAME_DELAY: Duration = Duration::from_millis(100);
+/// The maximum number of pixels decoded from all frames of a single image, which is around 500 frames of an image the size of the largest keys.
+const MAX_DECODED_PIXELS: u64 = 50_000_000;
+/// The maximum number of converted animations kept in memory.
+const MAX_CACHED: usize = 32;
+
+/// A frame already converted to the image format of a device.
+pub struct Frame {
+	pub data: Vec<u8>,
+	pub delay: Duration,
+}
+
+pub type Frames = Arc<[Frame]>;
+
+/// Converted animations keyed by a hash of the source image and the image format they were converted to, along with the order they were added in.
+type Cache = (HashMap<(u64, String), Frames>, VecDeque<(u64, String)>);
+
+static CACHE: LazyLock<Mutex<Cache>> = LazyLock::new(|| Mutex::new((HashMap::new(), VecDeque::new())));
+
+/// Start decoding the frames of an image, returning `None` if it is not in an animated format.
+fn decode_frames(bytes: &[u8]) -> Result<Option<image::Frames<'_>>, image::ImageError> {
+	let Ok(format) = image::guess_format(bytes) else { return Ok(None) };
+	Ok(Some(match format {
+		ImageFormat::Gif => image::codecs::gif::GifDecoder::new(Cursor::new(bytes))?.into_frames(),
+		ImageFormat::Png => {
+			let decoder = image::codecs::png::PngDecoder::new(Cursor::new(bytes))?;
+			if !decoder.is_apng()? {
+				return Ok(None);
+			}
+			decoder.apng()?.into_frames()
+		}
+		ImageFormat::WebP => {
+			let decoder = image::codecs::webp::WebPDecoder::new(Cursor::new(bytes))?;
+			if !decoder.has_animation() {
+				return Ok(None);
+			}
+			decoder.into_frames()
+		}
+		_ => return Ok(None),
+	}))
+}
+
+fn is_animated(bytes: &[u8]) -> Result<bool, image::ImageError> {
+	Ok(match image::guess_format(bytes) {
+		Ok(ImageFormat::Gif) => true,
+		Ok(ImageFormat::Png) => image::codecs::png::PngDecoder::new(Cursor::new(bytes))?.is_apng()?,
+		Ok(ImageFormat::WebP) => image::codecs::webp::WebPDecoder::new(Cursor::new(bytes))?.has_animation(),
+		_ => false,
+	})
+}
+
+/// Replace an animated image data URL with one of its first frame, for devices that cannot play animations.
+pub fn first_frame(image: String) -> Result<String, anyhow::Error> {
+	let Some(data) = image.split_once(',').filter(|(header, _)| header.ends_with(";base64")).map(|(_, data)| data) else {
+		return Ok(image);
+	};
+	let bytes = base64::engine::general_purpose::STANDARD.decode(data)?;
+	if !is_animated(&bytes)? {
+		return Ok(image);
+	}
+
+	let mut png = vec![];
+	image::load_from_memory(&bytes)?.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+	Ok(format!("data:image/png;base64,{}", base64::engine::general_purpose::STANDARD.encode(png)))
+}
+
+/// Decode an image and convert each of its frames with `convert`, returning `None` if the image is not animated.
+///
+/// `format` should uniquely identify the output of `convert`, as converted frames are cached.
+pub async fn load(bytes: &[u8], format: String, convert: impl Fn(DynamicImage) -> Result<Vec<u8>, anyhow::Error> + Send + 'static) -> Result<Option<Frames>, anyhow::Error> {
+	let key = (crate::image_cache::content_hash(bytes), format);
+	if let Some(frames) = CACHE.lock().unwrap().0.get(&key) {
+		return Ok(Some(frames.clone()));
+	}
+
+	let bytes = bytes.to_vec();
+	let frames = tokio::task::spawn_blocking(move || -> Result<Option<Frames>, anyhow::Error> {
+		let Some(frames) = decode_frames(&bytes)? else { return Ok(None) };
+		// Each frame is converted as soon as it is decoded, so that only one frame is held at full size.
+		let mut converted = vec![];
+		let mut pixels = 0;
+		for frame in frames {
+			let frame = frame?;
+			pixels += u64::from(frame.buffer().width()) * u64::from(frame.buffer().height());
+			if pixels > MAX_DECODED_PIXELS && !converted.is_empty() {
+				break;
+			}
+			let (numerator, denominator) = frame.delay().numer_denom_ms();
+			let delay = Duration::from_millis((numerator / denominator.max(1)) as u64);
+			converted.push(Frame {
+				data: convert(DynamicImage::ImageRgba8(frame.into_buffer()))?,
+				delay: if delay <= Duration::from_millis(10) { DEFAULT_FRAME_DELAY } else { delay },
+			});
+		}
+		Ok(if converted.len() > 1 { Some(converted.into()) } else { None })
+	})
+	.await??;
+
+	if let Some(frames) = &frames {
+		let (cache, order) = &mut *CACHE.lock().unwrap();
+		if cache.insert(key.clone(), frames.clone()).is_none() {
+			order.push_back(key);
+		}
+		while order.len() > MAX_CACHED {
+			if let Some(oldest) = order.pop_front() {
+				cache.remove(&oldest);
+			}
+		}
+	}
+	Ok(frames)
+}
+
+struct Animation {
+	frames: Frames,
+	index: usize,
+	due: Instant,
+}
+
+#[derive(Default)]
+struct DeviceAnimations {
+	keys: HashMap<u8, Animation>,
+	paused: bool,
+	/// Woken whenever the animations change, so that the scheduler can recalculate when the next frame is due.
+	notify: Arc<Notify>,
+}
+
+static SCHEDULERS: LazyLock<Mutex<HashMap<String, DeviceAnimations>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn is_current(device: &str, position: u8, frames: &Frames) -> bool {
+	SCHEDULERS
+		.lock()
+		.unwrap()
+		.get(device)
+		.and_then(|animations| animations.keys.get(&position))
+		.is_some_and(|animation| Arc::ptr_eq(&animation.frames, frames))
+}
+
+/// Animate a key whose first frame has already been written, starting the scheduler for its device if necessary.
+pub fn start(device: &str, position: u8, frames: Frames) {
+	let mut schedulers = SCHEDULERS.lock().unwrap();
+	let spawn = !schedulers.contains_key(device);
+	let animations = schedulers.entry(device.to_owned()).or_insert_with(|| DeviceAnimations {
+		paused: crate::device_sleep::is_device_sleeping(device),
+		..Default::default()
+	});
+	let due = Instant::now() + frames[0].delay.max(MIN_FRAME_INTERVAL);
+	animations.keys.insert(position, Animation { frames, index: 0, due });
+	animations.notify.notify_one();
+
+	if spawn {
+		tokio::spawn(run(device.to_owned(), animations.notify.clone()));
+	}
+}
+
+/// Stop animating a key, for example because its image has been replaced or cleared.
+pub fn stop(device: &str, position: u8) {
+	if let Some(animations) = SCHEDULERS.lock().unwrap().get_mut(device)
+		&& animations.keys.remove(&position).is_some()
+	{
+		animations.notify.notify_one();
+	}
+}
+
+/// Stop animating all keys of a device, for example because its profile has changed or it has been disconnected.
+pub fn stop_device(device: &str) {
+	if let Some(animations) = SCHEDULERS.lock().unwrap().get_mut(device) {
+		animations.keys.clear();
+		animations.notify.notify_one();
+	}
+}
+
+/// Pause or resume the animations of a device while it is asleep.
+pub fn set_paused(device: &str, paused: bool) {
+	if let Some(animations) = SCHEDULERS.lock().unwrap().get_mut(device) {
+		animations.paused = paused;
+		if !paused {
+			let now = Instant::now();
+			for animation in animations.keys.values_mut() {
+				animation.due = animation.due.max(now);
+			}
+		}
+		animations.notify.notify_one();
+	}
+}
+
+/// Write frames to the keys of a device as they become due, exiting once it has no animations left.
+async fn run(device: String, notify: Arc<Notify>) {
+	loop {
+		let next = {
+			let mut schedulers = SCHEDULERS.lock().unwrap();
+			let Some(animations) = schedulers.get(&device) else { return };
+			if animations.keys.is_empty() {
+				schedulers.remove(&device);
+				return;
+			}
+			if animations.paused { None } else { animations.keys.values().map(|v| v.due).min() }
+		};
+
+		match next {
+			Some(next) => {
+				tokio::select! {
+					_ = tokio::time::sleep_until(next) => {}
+					_ = notify.notified() => continue,
+				}
+			}
+			None => {
+				notify.notified().await;
+				continue;
+			}
+		}
+
+		let now = Instant::now();
+		let due = {
+			let mut schedulers = SCHEDULERS.lock().unwrap();
+			let Some(animations) = schedulers.get_mut(&device) else { return };
+			if animations.paused {
+				continue;
+			}
+			let mut due = vec![];
+			for (position, animation) in animations.keys.iter_mut().filter(|(_, v)| v.due <= now) {
+				animation.index = (animation.index + 1) % animation.frames.len();
+				let delay = animation.frames[animation.index].delay.max(MIN_FRAME_INTERVAL);
+				// Skip ahead rather than trying to catch up if frames could not be written in time.
+				let next = animation.due + delay;
+				animation.due = if next < now { now + delay } else { next };
+				due.push((*position, animation.frames.clone(), animation.index));
+			}
+			due
+		};
+
+		let Some(_writes) = crate::elgato::lock_writes(&device).await else { continue };
+		let due = due.into_iter().filter(|(position, frames, _)| is_current(&device, *position, frames)).collect::<Vec<_>>();
+		if let Err(error) = crate::elgato::write_frames(&device, due.iter().map(|(position, frames, index)| (*position, &frames[*index].data[..]))).await {
+			log::warn!("Failed to write animation frames to device {device}: {error:#}");
+		}
+	}
+}
@@ -1,70 +1,216 @@
+use crate::schedules::{Clock, SCHEDULES, ScheduleRules, SystemClock, active_rules, scheduled_profile};
+use crate::store::migrations::{APPLICATION_PROFILES_MIGRATIONS, Migration};
 use crate::store::{NotProfile, Store};
 
 use std::collections::HashMap;
 use std::sync::LazyLock;
 
 use active_win_pos_rs::get_active_window;
+use anyhow::anyhow;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
 use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, RefreshKind, System};
 use tauri::{Emitter, Manager};
 use tokio::sync::RwLock;
 
-pub type ApplicationProfiles = HashMap<String, HashMap<String, String>>;
-impl NotProfile for ApplicationProfiles {}
+/// The property of the focused window that an application rule matches against.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchField {
+	AppName,
+	WindowTitle,
+	ProcessPath,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchMode {
+	Exact,
+	Substring,
+	/// A pattern matching the whole value, where `*` matches any sequence of characters and `?` matches any single character.
+	Glob,
+	Regex,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ApplicationRule {
+	pub field: MatchField,
+	pub mode: MatchMode,
+	pub pattern: String,
+	#[serde(default)]
+	pub case_sensitive: bool,
+	/// The profile to select on each device while the rule matches the focused window.
+	pub profiles: HashMap<String, String>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApplicationProfiles {
+	/// Rules in order of priority, of which the first that matches the focused window and has a profile for a device is used.
+	pub rules: Vec<ApplicationRule>,
+	/// The profile to select on each device when no rule matches and no schedule applies.
+	pub default: HashMap<String, String>,
+}
+
+impl NotProfile for ApplicationProfiles {
+	const MIGRATIONS: &'static [Migration] = APPLICATION_PROFILES_MIGRATIONS;
+}
+
+/// The properties of the focused window that application rules can match against.
+#[derive(Clone, Default, PartialEq)]
+pub struct FocusedWindow {
+	pub app_name: String,
+	pub title: String,
+	pub process_path: String,
+}
+
+impl ApplicationRule {
+	fn regex(&self) -> Result<Regex, regex::Error> {
+		let pattern = match self.mode {
+			MatchMode::Exact => format!("^{}$", regex::escape(&self.pattern)),
+			MatchMode::Substring => regex::escape(&self.pattern),
+			MatchMode::Glob => {
+				let pattern = self.pattern.chars().fold(String::new(), |mut pattern, c| {
+					match c {
+						'*' => pattern.push_str(".*"),
+						'?' => pattern.push('.'),
+						c => pattern.push_str(&regex::escape(&c.to_string())),
+					}
+					pattern
+				});
+				format!("^{pattern}$")
+			}
+			MatchMode::Regex => self.pattern.clone(),
+		};
+		RegexBuilder::new(&pattern).case_insensitive(!self.case_sensitive).build()
+	}
+
+	/// Check that the pattern of the rule is valid.
+	pub fn validate(&self) -> Result<(), anyhow::Error> {
+		self.regex().map(|_| ()).map_err(|error| anyhow!("invalid pattern \"{}\": {error}", self.pattern))
+	}
+}
+
+/// An application rule with its pattern compiled, so that it is not compiled again each time the focused window is checked.
+pub struct CompiledRule {
+	field: MatchField,
+	/// The compiled pattern, which is `None` for rules with empty or invalid patterns as they never match.
+	regex: Option<Regex>,
+}
+
+impl CompiledRule {
+	fn new(rule: &ApplicationRule) -> Self {
+		Self {
+			field: rule.field,
+			regex: if rule.pattern.is_empty() { None } else { rule.regex().ok() },
+		}
+	}
+
+	fn matches(&self, window: &FocusedWindow) -> bool {
+		let value = match self.field {
+			MatchField::AppName => &window.app_name,
+			MatchField::WindowTitle => &window.title,
+			MatchField::ProcessPath => &window.process_path,
+		};
+		self.regex.as_ref().is_some_and(|regex| regex.is_match(value))
+	}
+}
+
+pub fn compile_rules(rules: &[ApplicationRule]) -> Vec<CompiledRule> {
+	rules.iter().map(CompiledRule::new).collect()
+}
+
+/// Get the indices of the application rules that match a window.
+fn matching_rules(rules: &[CompiledRule], window: &FocusedWindow) -> Vec<usize> {
+	rules.iter().enumerate().filter(|(_, rule)| rule.matches(window)).map(|(index, _)| index).collect()
+}
 
 pub static APPLICATIONS: RwLock<Vec<String>> = RwLock::const_new(Vec::new());
-pub static APPLICATION_PROFILES: LazyLock<RwLock<Store<ApplicationProfiles>>> = LazyLock::new(|| RwLock::new(Store::new("applications", &crate::shared::config_dir(), HashMap::new()).unwrap()));
+pub static APPLICATION_PROFILES: LazyLock<RwLock<Store<ApplicationProfiles>>> =
+	LazyLock::new(|| RwLock::new(Store::new("applications", &crate::shared::config_dir(), ApplicationProfiles::default()).unwrap()));
+/// The compiled rules of `APPLICATION_PROFILES` in the same order, which must be replaced while holding its write lock whenever the rules change.
+pub static COMPILED_RULES: RwLock<Vec<CompiledRule>> = RwLock::const_new(Vec::new());
 
 pub static APPLICATION_PROCESSES: LazyLock<RwLock<HashMap<String, Vec<u32>>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
 pub static APPLICATION_PLUGINS: LazyLock<RwLock<HashMap<String, Vec<String>>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
 
-#[derive(Clone, serde::Serialize)]
+#[derive(Clone, Serialize)]
 pub struct SwitchProfileEvent {
 	device: String,
 	profile: String,
 }
 
-pub fn init_application_watcher() {
-	tokio::spawn(async move {
-		let mut previous = String::new();
-		let app_handle = crate::APP_HANDLE.get().unwrap();
-		loop {
-			let app_name = if let Ok(win) = get_active_window() {
-				let mut applications = APPLICATIONS.write().await;
-				if !applications.contains(&win.app_name) && !win.app_name.to_lowercase().starts_with(&crate::shared::PRODUCT_NAME.to_lowercase()) && !win.app_name.trim().is_empty() {
-					applications.push(win.app_name.clone());
-					let _ = app_handle.get_webview_window("main").unwrap().emit("applications", applications.clone());
-				}
-				win.app_name
-			} else {
-				String::new()
-			};
-
-			if app_name != previous {
-				let application_profiles = &APPLICATION_PROFILES.read().await.value;
-				let application = application_profiles.get(&app_name);
-				let default = application_profiles.get("opendeck_default");
-				for value in crate::shared::DEVICES.iter() {
-					let device = value.key();
-					let Some(profile) = application.and_then(|d| d.get(device)).or(default.and_then(|d| d.get(device))) else {
-						continue;
-					};
-					if crate::store::profiles::DEVICE_STORES.write().await.get_selected_profile(device).ok().as_ref() == Some(profile) {
-						continue;
-					}
-					let _ = app_handle.get_webview_window("main").unwrap().emit(
-						"switch_profile",
-						SwitchProfileEvent {
-							device: device.clone(),
-							profile: profile.clone(),
-						},
-					);
-				}
-				previous = app_name;
+/// Get the profile that should be selected on a device, preferring the profile of the first matching application rule,
+/// then that of the first active schedule rule, and then the default profile.
+fn target_profile<'a>(application_profiles: &'a ApplicationProfiles, matching: &[usize], schedules: &'a ScheduleRules, active_schedules: &[usize], device: &str) -> Option<&'a String> {
+	matching
+		.iter()
+		.find_map(|index| application_profiles.rules.get(*index)?.profiles.get(device))
+		.or_else(|| scheduled_profile(schedules, active_schedules, device))
+		.or_else(|| application_profiles.default.get(device))
+}
+
+/// Switch profiles when the focused application or the rules matching it change, or a schedule rule starts or stops applying, using a clock that can be replaced.
+async fn watch_applications(clock: impl Clock) {
+	let mut previous = String::new();
+	let mut previous_matching = None;
+	let mut previous_schedules = None;
+	let app_handle = crate::APP_HANDLE.get().unwrap();
+	{
+		let store = APPLICATION_PROFILES.read().await;
+		*COMPILED_RULES.write().await = compile_rules(&store.value.rules);
+	}
+	loop {
+		let window = if let Ok(win) = get_active_window() {
+			let mut applications = APPLICATIONS.write().await;
+			if !applications.contains(&win.app_name) && !win.app_name.to_lowercase().starts_with(&crate::shared::PRODUCT_NAME.to_lowercase()) && !win.app_name.trim().is_empty() {
+				applications.push(win.app_name.clone());
+				let _ = app_handle.get_webview_window("main").unwrap().emit("applications", applications.clone());
+			}
+			FocusedWindow {
+				app_name: win.app_name,
+				title: win.title,
+				process_path: win.process_path.to_string_lossy().into_owned(),
 			}
+		} else {
+			FocusedWindow::default()
+		};
 
-			tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+		let store = APPLICATION_PROFILES.read().await;
+		let matching = matching_rules(&COMPILED_RULES.read().await, &window);
+		let application_profiles = store.value.clone();
+		drop(store);
+		let schedules = SCHEDULES.read().await.value.clone();
+		let active_schedules = active_rules(&schedules, clock.now());
+		// The window title changes often, so profiles are only switched again when that changes which rules match.
+		if window.app_name != previous || previous_matching.as_ref() != Some(&matching) || previous_schedules.as_ref() != Some(&active_schedules) {
+			for value in crate::shared::DEVICES.iter() {
+				let device = value.key();
+				let Some(profile) = target_profile(&application_profiles, &matching, &schedules, &active_schedules, device) else {
+					continue;
+				};
+				if crate::store::profiles::DEVICE_STORES.write().await.get_selected_profile(device).ok().as_ref() == Some(profile) {
+					continue;
+				}
+				let _ = app_handle.get_webview_window("main").unwrap().emit(
+					"switch_profile",
+					SwitchProfileEvent {
+						device: device.clone(),
+						profile: profile.clone(),
+					},
+				);
+			}
+			previous = window.app_name;
+			previous_matching = Some(matching);
+			previous_schedules = Some(active_schedules);
 		}
-	});
+
+		tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+	}
+}
+
+pub fn init_application_watcher() {
+	tokio::spawn(watch_applications(SystemClock));
 
 	tokio::spawn(async move {
 		let mut system = System::new_with_specifics(RefreshKind::nothing().with_processes(ProcessRefreshKind::nothing().without_tasks()));
@@ -131,3 +277,87 @@ pub async fn stop_monitoring(plugin: &str) {
 	}
 	application_plugins.retain(|_, p| !p.is_empty());
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::schedules::ScheduleRule;
+
+	use chrono::{NaiveDate, NaiveDateTime};
+
+	struct FixedClock(NaiveDateTime);
+
+	impl Clock for FixedClock {
+		fn now(&self) -> NaiveDateTime {
+			self.0
+		}
+	}
+
+	fn profiles(device: &str, profile: &str) -> HashMap<String, String> {
+		HashMap::from([(device.to_owned(), profile.to_owned())])
+	}
+
+	fn application_profiles() -> ApplicationProfiles {
+		ApplicationProfiles {
+			rules: vec![ApplicationRule {
+				field: MatchField::AppName,
+				mode: MatchMode::Exact,
+				pattern: "Editor".to_owned(),
+				case_sensitive: false,
+				profiles: profiles("sd-1", "Editing"),
+			}],
+			default: profiles("sd-1", "Default"),
+		}
+	}
+
+	fn schedules() -> ScheduleRules {
+		vec![ScheduleRule {
+			days: vec![],
+			start: "09:00".to_owned(),
+			end: "17:00".to_owned(),
+			profiles: profiles("sd-1", "Work"),
+		}]
+	}
+
+	fn window(app_name: &str) -> FocusedWindow {
+		FocusedWindow {
+			app_name: app_name.to_owned(),
+			..Default::default()
+		}
+	}
+
+	fn at(time: &str) -> FixedClock {
+		FixedClock(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_time(chrono::NaiveTime::parse_from_str(time, "%H:%M").unwrap()))
+	}
+
+	/// Get the profile selected on the device `sd-1` for a focused application at the time of a clock.
+	fn select(app_name: &str, clock: &impl Clock) -> Option<String> {
+		let application_profiles = application_profiles();
+		let schedules = schedules();
+		let matching = matching_rules(&compile_rules(&application_profiles.rules), &window(app_name));
+		let active = active_rules(&schedules, clock.now());
+		target_profile(&application_profiles, &matching, &schedules, &active, "sd-1").cloned()
+	}
+
+	#[test]
+	fn applications_take_precedence_over_schedules() {
+		assert_eq!(select("editor", &at("10:00")).as_deref(), Some("Editing"));
+		assert_eq!(select("editor", &at("20:00")).as_deref(), Some("Editing"));
+	}
+
+	#[test]
+	fn schedules_take_precedence_over_default() {
+		assert_eq!(select("Browser", &at("10:00")).as_deref(), Some("Work"));
+		assert_eq!(select("Browser", &at("17:00")).as_deref(), Some("Default"));
+	}
+
+	#[test]
+	fn devices_without_profiles_are_left_alone() {
+		let application_profiles = application_profiles();
+		let schedules = schedules();
+		let matching = matching_rules(&compile_rules(&application_profiles.rules), &window("Editor"));
+		let active = active_rules(&schedules, at("10:00").now());
+		assert_eq!(target_profile(&application_profiles, &matching, &schedules, &active, "sd-2"), None);
+	}
+}
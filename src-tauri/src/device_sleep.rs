@@ -33,8 +33,17 @@ pub fn init_device_sleep() {
 pub async fn update_sleep_timeout_minutes(minutes: u16) -> Result<(), anyhow::Error> {
 	SLEEP_TIMEOUT_MINUTES.store(minutes, Ordering::Relaxed);
 
-	if minutes == 0 && !(SLEEP_WHEN_COMPUTER_LOCKED.load(Ordering::Relaxed) && COMPUTER_LOCKED.load(Ordering::Relaxed)) {
-		for device in SLEEPING_DEVICES.iter().map(|entry| entry.key().clone()).collect::<Vec<_>>() {
+	wake_devices_without_timeout().await
+}
+
+/// Wake sleeping devices whose effective sleep timeout is now disabled.
+pub async fn wake_devices_without_timeout() -> Result<(), anyhow::Error> {
+	if SLEEP_WHEN_COMPUTER_LOCKED.load(Ordering::Relaxed) && COMPUTER_LOCKED.load(Ordering::Relaxed) {
+		return Ok(());
+	}
+
+	for device in SLEEPING_DEVICES.iter().map(|entry| entry.key().clone()).collect::<Vec<_>>() {
+		if sleep_timeout_minutes(&device).await == 0 {
 			wake_device(&device).await?;
 		}
 	}
@@ -42,6 +51,11 @@ pub async fn update_sleep_timeout_minutes(minutes: u16) -> Result<(), anyhow::Er
 	Ok(())
 }
 
+async fn sleep_timeout_minutes(device: &str) -> u16 {
+	let settings = crate::store::profiles::get_device_settings(device).await;
+	settings.sleep_timeout_minutes.unwrap_or_else(|| SLEEP_TIMEOUT_MINUTES.load(Ordering::Relaxed))
+}
+
 pub async fn note_activity(device: &str) -> Result<bool, anyhow::Error> {
 	if SLEEP_WHEN_COMPUTER_LOCKED.load(Ordering::Relaxed) && COMPUTER_LOCKED.load(Ordering::Relaxed) {
 		return Ok(true);
@@ -58,21 +72,22 @@ pub fn deregister_device(device: &str) {
 
 pub async fn sleep_device(device: String) -> Result<(), anyhow::Error> {
 	crate::events::outbound::devices::set_device_brightness(&device, 0).await?;
+	crate::animation::set_paused(&device, true);
 	SLEEPING_DEVICES.insert(device, ());
 	Ok(())
 }
 
 async fn sleep_idle_devices() -> Result<(), anyhow::Error> {
-	let timeout = SLEEP_TIMEOUT_MINUTES.load(Ordering::Relaxed);
-	if timeout == 0 {
-		return Ok(());
-	}
-
-	let idle_after = Duration::from_secs(timeout as u64 * 60);
 	let now = Instant::now();
 	let device_ids = LAST_ACTIVITY.iter().map(|entry| entry.key().clone()).collect::<Vec<_>>();
 
 	for device in device_ids {
+		let timeout = sleep_timeout_minutes(&device).await;
+		if timeout == 0 {
+			continue;
+		}
+
+		let idle_after = Duration::from_secs(timeout as u64 * 60);
 		let Some(last_activity) = LAST_ACTIVITY.get(&device).map(|entry| *entry.value()) else { continue };
 		if now.duration_since(last_activity) < idle_after || is_device_sleeping(&device) {
 			continue;
@@ -86,7 +101,8 @@ async fn sleep_idle_devices() -> Result<(), anyhow::Error> {
 
 pub async fn wake_device(device: &str) -> Result<bool, anyhow::Error> {
 	if SLEEPING_DEVICES.remove(device).is_some() {
-		let brightness = crate::store::get_settings().value.brightness;
+		crate::animation::set_paused(device, false);
+		let brightness = crate::store::profiles::get_device_settings(device).await.brightness();
 		crate::events::outbound::devices::set_device_brightness(device, brightness).await?;
 		return Ok(true);
 	}
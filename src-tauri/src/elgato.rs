@@ -4,8 +4,10 @@ use crate::events::inbound;
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::sync::LazyLock;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use base64::Engine as _;
+use dashmap::DashMap;
 use elgato_streamdeck::{
 	AsyncStreamDeck, DeviceStateUpdate,
 	images::{ImageRect, convert_image_with_format_async},
@@ -14,7 +16,28 @@ use elgato_streamdeck::{
 use image::GenericImageView as _;
 use tokio::sync::RwLock;
 
-static ELGATO_DEVICES: LazyLock<RwLock<HashMap<String, AsyncStreamDeck>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+/// A connected device, along with the generation of the reader loop that owns it.
+struct ElgatoDevice {
+	device: AsyncStreamDeck,
+	/// Distinguishes a device that was reconnected under the same ID from the connection it replaced.
+	generation: u64,
+	/// Held while writing to keys, so that a frame that became due just before an animation was stopped cannot overwrite the image that replaced it.
+	writes: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl std::ops::Deref for ElgatoDevice {
+	type Target = AsyncStreamDeck;
+
+	fn deref(&self) -> &Self::Target {
+		&self.device
+	}
+}
+
+static ELGATO_DEVICES: LazyLock<RwLock<HashMap<String, ElgatoDevice>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(0);
+/// The most recent update to each key, so that an image which took longer to convert does not replace one set after it.
+static KEY_UPDATES: LazyLock<DashMap<(String, u8), u64>> = LazyLock::new(DashMap::new);
+static NEXT_KEY_UPDATE: AtomicU64 = AtomicU64::new(0);
 static HIDAPI: LazyLock<RwLock<Option<Arc<hidapi::HidApi>>>> = LazyLock::new(|| RwLock::new(None));
 
 /// Extract the average colour from an image.
@@ -26,14 +49,58 @@ fn extract_average_colour(img: &image::DynamicImage) -> (u8, u8, u8) {
 	((r_sum / count) as u8, (g_sum / count) as u8, (b_sum / count) as u8)
 }
 
+/// Take the lock held while writing to the keys of a device, if it is connected.
+pub async fn lock_writes(id: &str) -> Option<tokio::sync::OwnedMutexGuard<()>> {
+	let writes = ELGATO_DEVICES.read().await.get(id)?.writes.clone();
+	Some(writes.lock_owned().await)
+}
+
 pub async fn update_image(context: &crate::shared::Context, image: Option<&str>) -> Result<(), anyhow::Error> {
+	let Some(kind) = ELGATO_DEVICES.read().await.get(&context.device).map(|device| device.kind()) else {
+		return Ok(());
+	};
+	if !kind.is_visual() {
+		return Ok(());
+	}
+	let key_count = kind.key_count();
+	let is_touch_point = context.controller == "Keypad" && context.position >= key_count;
+	let is_key = context.controller == "Keypad" && !is_touch_point;
+
+	let update = NEXT_KEY_UPDATE.fetch_add(1, Ordering::Relaxed);
+	if is_key {
+		KEY_UPDATES.insert((context.device.clone(), context.position), update);
+	}
+
+	// Decode and convert key images before taking the write lock, as animations can take a while to convert.
+	let key_image: Option<(Arc<[u8]>, Option<crate::animation::Frames>)> = match image {
+		Some(image) if is_key => {
+			let data = image.split_once(',').unwrap().1;
+			let bytes = base64::engine::general_purpose::STANDARD.decode(data)?;
+			let format = kind.key_image_format();
+			let format_key = format!("{kind:?}/{:?}", format.rotation);
+			Some(
+				match crate::animation::load(&bytes, format_key.clone(), move |img| Ok(convert_image_with_format_async(format, img)?)).await? {
+					Some(frames) => (frames[0].data.as_slice().into(), Some(frames)),
+					None => (
+						crate::image_cache::convert(&bytes, &format_key, |bytes| Ok(convert_image_with_format_async(format, image::load_from_memory(bytes)?)?))?,
+						None,
+					),
+				},
+			)
+		}
+		_ => None,
+	};
+
+	let Some(_writes) = lock_writes(&context.device).await else {
+		return Ok(());
+	};
+	if is_key && KEY_UPDATES.get(&(context.device.clone(), context.position)).is_some_and(|latest| *latest != update) {
+		return Ok(());
+	}
 	if let Some(device) = ELGATO_DEVICES.read().await.get(&context.device) {
-		let kind = device.kind();
-		if !kind.is_visual() {
-			return Ok(());
+		if is_key {
+			crate::animation::stop(&context.device, context.position);
 		}
-		let key_count = kind.key_count();
-		let is_touch_point = context.controller == "Keypad" && context.position >= key_count;
 
 		if let Some(image) = image {
 			let data = image.split_once(',').unwrap().1;
@@ -51,17 +118,34 @@ pub async fn update_image(context: &crate::shared::Context, image: Option<&str>)
 				};
 				device.write_lcd(context.position as u16 * 200, 0, &ImageRect::from_image_async(img)?).await?;
 			} else if context.controller == "Infobar" {
-				let img = image::load_from_memory(&bytes)?;
 				let Some(format) = device.kind().lcd_image_format() else {
 					return Err(anyhow::anyhow!("Failed to get LCD image format"));
 				};
-				let data = convert_image_with_format_async(format, img.resize_exact(248, 58, image::imageops::FilterType::Lanczos3))?;
-				device.write_lcd_fill(&data).await?;
+				let data = crate::image_cache::convert(&bytes, &format!("{kind:?}/{:?}/infobar", format.rotation), |bytes| {
+					let img = image::load_from_memory(bytes)?;
+					Ok(convert_image_with_format_async(format, img.resize_exact(248, 58, image::imageops::FilterType::Lanczos3))?)
+				})?;
+				if !crate::image_cache::should_write(&context.device, &context.controller, context.position, &data) {
+					return Ok(());
+				}
+				if let Err(error) = device.write_lcd_fill(&data).await {
+					crate::image_cache::forget(&context.device, &context.controller, context.position);
+					return Err(error.into());
+				}
 			} else if is_touch_point {
 				let (r, g, b) = extract_average_colour(&image::load_from_memory(&bytes)?);
 				device.set_touchpoint_color(context.position - key_count, r, g, b).await?;
-			} else {
-				device.set_button_image(context.position, image::load_from_memory(&bytes)?).await?;
+			} else if let Some((data, frames)) = key_image {
+				if let Some(frames) = frames {
+					crate::animation::start(&context.device, context.position, frames);
+				}
+				if !crate::image_cache::should_write(&context.device, &context.controller, context.position, &data) {
+					return Ok(());
+				}
+				if let Err(error) = device.write_image(context.position, &data).await {
+					crate::image_cache::forget(&context.device, &context.controller, context.position);
+					return Err(error.into());
+				}
 			}
 		} else if context.controller == "Encoder" {
 			let mut img = image::DynamicImage::new_rgb8(200, 100);
@@ -81,10 +165,12 @@ pub async fn update_image(context: &crate::shared::Context, image: Option<&str>)
 			};
 			let data = convert_image_with_format_async(format, image::DynamicImage::new_rgb8(248, 58))?;
 			device.write_lcd_fill(&data).await?;
+			crate::image_cache::forget(&context.device, &context.controller, context.position);
 		} else if is_touch_point {
 			device.set_touchpoint_color(context.position - key_count, 0, 0, 0).await?;
 		} else {
 			device.clear_button_image(context.position).await?;
+			crate::image_cache::forget(&context.device, &context.controller, context.position);
 		}
 		device.flush().await?;
 	}
@@ -98,7 +184,27 @@ async fn clear_all_touchpoints(device: &AsyncStreamDeck) {
 	}
 }
 
+/// Write frames that have already been converted to the key image format of a device.
+///
+/// The caller must hold the lock returned by [`lock_writes`].
+pub async fn write_frames(id: &str, frames: impl Iterator<Item = (u8, &[u8])>) -> Result<(), anyhow::Error> {
+	if let Some(device) = ELGATO_DEVICES.read().await.get(id) {
+		for (position, data) in frames {
+			if crate::image_cache::should_write(id, "Keypad", position, data) {
+				device.write_image(position, data).await?;
+			}
+		}
+		device.flush().await?;
+	}
+	Ok(())
+}
+
 pub async fn clear_screen(id: &str) -> Result<(), anyhow::Error> {
+	let Some(_writes) = lock_writes(id).await else {
+		return Ok(());
+	};
+	crate::animation::stop_device(id);
+	crate::image_cache::forget_device(id);
 	if let Some(device) = ELGATO_DEVICES.read().await.get(id) {
 		device.clear_all_button_images().await?;
 		if let Some(lcd_format) = device.kind().lcd_image_format() {
@@ -122,8 +228,14 @@ pub async fn set_brightness(id: &str, brightness: u8) {
 	}
 }
 
+/// Get the kind of a connected Elgato device.
+pub async fn device_kind(id: &str) -> Option<Kind> {
+	ELGATO_DEVICES.read().await.get(id).map(|v| v.device.kind())
+}
+
 pub async fn reset_devices() {
-	for (_id, device) in ELGATO_DEVICES.read().await.iter() {
+	for (id, device) in ELGATO_DEVICES.read().await.iter() {
+		crate::image_cache::forget_device(id);
 		let _ = device.reset().await;
 		let _ = device.flush().await;
 	}
@@ -147,11 +259,19 @@ async fn init(device: AsyncStreamDeck, device_id: String) {
 	};
 	let _ = device.clear_all_button_images().await;
 	clear_all_touchpoints(&device).await;
-	let _ = device.set_brightness(crate::store::get_settings().value.brightness).await;
+	let _ = device.set_brightness(crate::store::profiles::get_device_settings(&device_id).await.brightness()).await;
 	let _ = device.flush().await;
 
 	let reader = device.get_reader();
-	ELGATO_DEVICES.write().await.insert(device_id.clone(), device);
+	let generation = NEXT_GENERATION.fetch_add(1, Ordering::Relaxed);
+	ELGATO_DEVICES.write().await.insert(
+		device_id.clone(),
+		ElgatoDevice {
+			device,
+			generation,
+			writes: Arc::new(tokio::sync::Mutex::new(())),
+		},
+	);
 	let _ = clear_screen(&device_id).await;
 
 	crate::events::inbound::devices::register_device(
@@ -197,6 +317,10 @@ async fn init(device: AsyncStreamDeck, device_id: String) {
 			Ok(updates) => updates,
 			Err(_) => break,
 		};
+		// The device may have been dropped after being reported as unplugged, and possibly reconnected since.
+		if !ELGATO_DEVICES.read().await.get(&device_id).is_some_and(|device| device.generation == generation) {
+			break;
+		}
 		for update in updates {
 			match match update {
 				DeviceStateUpdate::ButtonDown(key) => inbound::devices::key_down(press(key)).await,
@@ -228,7 +352,17 @@ async fn init(device: AsyncStreamDeck, device_id: String) {
 		}
 	}
 
-	ELGATO_DEVICES.write().await.remove(&device_id);
+	{
+		let mut devices = ELGATO_DEVICES.write().await;
+		match devices.get(&device_id) {
+			Some(device) if device.generation == generation => drop(devices.remove(&device_id)),
+			// A newer connection to the same device now owns its ID, so it must not be deregistered.
+			Some(_) => return,
+			None => (),
+		}
+	}
+	crate::animation::stop_device(&device_id);
+	crate::image_cache::forget_device(&device_id);
 	crate::events::inbound::devices::deregister_device("", crate::events::inbound::PayloadEvent { payload: device_id })
 		.await
 		.unwrap();
@@ -275,3 +409,19 @@ pub async fn initialise_devices() {
 		}
 	}
 }
+
+/// Drop devices that are no longer connected, without waiting for reads from them to fail.
+pub async fn drop_disconnected_devices() {
+	let hid = match elgato_streamdeck::new_hidapi() {
+		Ok(hid) => hid,
+		Err(error) => {
+			log::warn!("Failed to initialise hidapi: {error}");
+			return;
+		}
+	};
+	let connected = elgato_streamdeck::asynchronous::list_devices_async(&hid)
+		.into_iter()
+		.map(|(_, serial)| format!("sd-{serial}"))
+		.collect::<Vec<_>>();
+	ELGATO_DEVICES.write().await.retain(|id, _| connected.contains(id));
+}
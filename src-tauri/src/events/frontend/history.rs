@@ -0,0 +1,263 @@
+//! A per-profile journal of edits made from the frontend, allowing them to be undone and redone for the rest of the session.
+
+use super::Error;
+
+use crate::shared::{ActionInstance, Context, DEVICES, Profile, config_dir, copy_dir, page_root};
+use crate::store::profiles::{LocksMut, acquire_locks_mut, get_slot_mut, save_profile_now};
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use tauri::command;
+
+/// The maximum number of edits that can be undone in each profile.
+const MAX_ENTRIES: usize = 50;
+/// How soon after each other edits in the same group must be made to be undone together, such as each keystroke of a title.
+const COALESCE_WINDOW: Duration = Duration::from_secs(1);
+
+/// The contents of a slot before and after an edit.
+struct SlotChange {
+	context: Context,
+	before: Option<ActionInstance>,
+	after: Option<ActionInstance>,
+}
+
+struct Entry {
+	changes: Vec<SlotChange>,
+	/// The image directories removed by the edit and where they are kept while it can be undone.
+	images: Vec<(PathBuf, PathBuf)>,
+	group: Option<String>,
+	recorded: Instant,
+}
+
+#[derive(Default)]
+struct Journal {
+	undo: VecDeque<Entry>,
+	redo: Vec<Entry>,
+}
+
+/// Journals keyed by device and root profile, so that edits within folders share the journal of their profile.
+static JOURNALS: LazyLock<DashMap<(String, String), Journal>> = LazyLock::new(DashMap::new);
+
+/// The directory that the images of removed instances are kept in while their removal can be undone, emptied on launch as journals only last for a session.
+static TRASH_DIR: LazyLock<PathBuf> = LazyLock::new(|| {
+	let dir = config_dir().join("history");
+	let _ = std::fs::remove_dir_all(&dir);
+	dir
+});
+static NEXT_TRASHED: AtomicU64 = AtomicU64::new(0);
+
+fn journal_key(device: &str, profile: &str) -> (String, String) {
+	(device.to_owned(), page_root(profile).to_owned())
+}
+
+/// Move a directory, copying it if it cannot be renamed, such as because the destination already exists.
+fn move_dir(from: &Path, to: &Path) {
+	if !from.exists() {
+		return;
+	}
+	if let Some(parent) = to.parent() {
+		let _ = std::fs::create_dir_all(parent);
+	}
+	if std::fs::rename(from, to).is_err() && copy_dir(from, to).is_ok() {
+		let _ = std::fs::remove_dir_all(from);
+	}
+}
+
+/// Delete the image directories kept for an entry that can no longer be undone.
+fn expire(entry: &Entry) {
+	for (_, trashed) in &entry.images {
+		let _ = std::fs::remove_dir_all(trashed);
+	}
+}
+
+async fn snapshot(context: &Context, locks: &mut LocksMut<'_>) -> Result<Option<ActionInstance>, anyhow::Error> {
+	Ok(get_slot_mut(context, locks).await?.clone())
+}
+
+/// An edit in progress, recording the slots it may change so that they can be compared once it is complete.
+pub struct PendingEdit {
+	before: Vec<(Context, Option<ActionInstance>)>,
+	images: Vec<(PathBuf, PathBuf)>,
+	group: Option<String>,
+}
+
+impl PendingEdit {
+	pub async fn begin(contexts: &[&Context], locks: &mut LocksMut<'_>) -> Result<Self, anyhow::Error> {
+		let mut before = vec![];
+		for context in contexts {
+			if before.iter().all(|(existing, _)| existing != *context) {
+				before.push(((*context).clone(), snapshot(context, locks).await?));
+			}
+		}
+		Ok(Self { before, images: vec![], group: None })
+	}
+
+	/// Remove a directory of images that the edit no longer references, keeping it until the edit can no longer be undone.
+	pub fn discard_images(&mut self, dir: PathBuf) {
+		if !dir.exists() {
+			return;
+		}
+		let trashed = TRASH_DIR.join(NEXT_TRASHED.fetch_add(1, Ordering::Relaxed).to_string());
+		move_dir(&dir, &trashed);
+		self.images.push((dir, trashed));
+	}
+
+	/// Merge this edit into the previous one if it belongs to the same group and follows it closely.
+	pub fn coalesce(mut self, group: String) -> Self {
+		self.group = Some(group);
+		self
+	}
+
+	/// Record the edit in the journal of its profile if it changed any slots.
+	pub async fn commit(self, locks: &mut LocksMut<'_>) -> Result<(), anyhow::Error> {
+		let mut changes = vec![];
+		for (context, before) in self.before {
+			let after = snapshot(&context, locks).await?;
+			if serde_json::to_value(&before)? != serde_json::to_value(&after)? {
+				changes.push(SlotChange { context, before, after });
+			}
+		}
+		let Some(first) = changes.first() else {
+			for (_, trashed) in &self.images {
+				let _ = std::fs::remove_dir_all(trashed);
+			}
+			return Ok(());
+		};
+
+		let mut journal = JOURNALS.entry(journal_key(&first.context.device, &first.context.profile)).or_default();
+		let merge = journal.redo.is_empty() && self.group.is_some() && journal.undo.back().is_some_and(|last| last.group == self.group && last.recorded.elapsed() < COALESCE_WINDOW);
+		// The images of undone entries are back in use, so they are not deleted along with the entries.
+		journal.redo.clear();
+
+		if merge {
+			let last = journal.undo.back_mut().unwrap();
+			for change in changes {
+				match last.changes.iter_mut().find(|v| v.context == change.context) {
+					Some(existing) => existing.after = change.after,
+					None => last.changes.push(change),
+				}
+			}
+			last.images.extend(self.images);
+			last.recorded = Instant::now();
+		} else {
+			journal.undo.push_back(Entry {
+				changes,
+				images: self.images,
+				group: self.group,
+				recorded: Instant::now(),
+			});
+			if journal.undo.len() > MAX_ENTRIES
+				&& let Some(expired) = journal.undo.pop_front()
+			{
+				expire(&expired);
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Discard the journal of a profile, for example because it has been deleted or renamed.
+pub fn forget(device: &str, profile: &str) {
+	if let Some((_, journal)) = JOURNALS.remove(&journal_key(device, profile)) {
+		journal.undo.iter().for_each(expire);
+	}
+}
+
+async fn disappear(instance: &ActionInstance) {
+	let _ = crate::events::outbound::will_appear::will_disappear(instance, true).await;
+	for child in instance.children.iter().flatten() {
+		let _ = crate::events::outbound::will_appear::will_disappear(child, false).await;
+	}
+}
+
+async fn appear(instance: &ActionInstance) {
+	let _ = crate::events::outbound::will_appear::will_appear(instance).await;
+	for child in instance.children.iter().flatten() {
+		let _ = crate::events::outbound::will_appear::will_appear(child).await;
+	}
+}
+
+/// Set the contents of the slots changed by an edit, sending events to plugins if they are on the page currently shown on the device.
+async fn apply(entry: &Entry, undo: bool, locks: &mut LocksMut<'_>) -> Result<(), anyhow::Error> {
+	for (original, trashed) in &entry.images {
+		if undo {
+			move_dir(trashed, original);
+		} else {
+			move_dir(original, trashed);
+		}
+	}
+
+	let changes = &entry.changes;
+	let mut ordered = changes.iter().collect::<Vec<_>>();
+	if undo {
+		ordered.reverse();
+	}
+	for change in ordered {
+		let selected = locks.device_stores.get_selected_profile(&change.context.device)? == change.context.profile;
+		let target = if undo { &change.before } else { &change.after };
+		let slot = get_slot_mut(&change.context, locks).await?;
+
+		if selected && let Some(old) = slot.as_ref() {
+			disappear(old).await;
+		}
+		*slot = target.clone();
+		if selected && let Some(new) = target {
+			appear(new).await;
+		}
+	}
+
+	let mut devices = changes.iter().map(|v| &v.context.device).collect::<Vec<_>>();
+	devices.dedup();
+	for device in devices {
+		save_profile_now(device, locks).await?;
+	}
+	Ok(())
+}
+
+async fn step(device: String, undo: bool) -> Result<Option<Profile>, Error> {
+	let mut locks = acquire_locks_mut().await;
+	if !DEVICES.contains_key(&device) {
+		return Err(Error::new(format!("device {device} not found")));
+	}
+
+	let selected_profile = locks.device_stores.get_selected_profile(&device)?;
+	let key = journal_key(&device, &selected_profile);
+	let entry = {
+		let Some(mut journal) = JOURNALS.get_mut(&key) else { return Ok(None) };
+		let entry = if undo { journal.undo.pop_back() } else { journal.redo.pop() };
+		let Some(mut entry) = entry else { return Ok(None) };
+		// Edits made after an undo or redo should never be merged into it.
+		entry.group = None;
+		entry
+	};
+
+	apply(&entry, undo, &mut locks).await?;
+
+	if let Some(mut journal) = JOURNALS.get_mut(&key) {
+		if undo {
+			journal.redo.push(entry);
+		} else {
+			journal.undo.push_back(entry);
+		}
+	}
+
+	let profile = locks.profile_stores.get_page(&DEVICES.get(&device).unwrap(), &selected_profile)?;
+	Ok(Some(profile.clone()))
+}
+
+/// Undo the most recent edit to the selected profile of a device, returning the updated page if there was one.
+#[command]
+pub async fn undo(device: String) -> Result<Option<Profile>, Error> {
+	step(device, true).await
+}
+
+/// Redo the most recently undone edit to the selected profile of a device, returning the updated page if there was one.
+#[command]
+pub async fn redo(device: String) -> Result<Option<Profile>, Error> {
+	step(device, false).await
+}
@@ -1,10 +1,12 @@
 use super::Error;
+use super::history::PendingEdit;
 
-use crate::shared::{Action, ActionContext, ActionInstance, ActionState, Context, config_dir};
+use crate::events::outbound::gestures::Gesture;
+use crate::events::outbound::multi_action::{Step, Trigger};
+use crate::shared::{Action, ActionContext, ActionInstance, ActionState, Context, PAGE_SEPARATOR, Profile, config_dir, copy_dir, page_images_path};
 use crate::store::profiles::{LocksMut, acquire_locks, acquire_locks_mut, get_instance_mut, get_slot, get_slot_mut, save_profile_now};
 
 use tauri::{AppHandle, Emitter, Manager, command};
-use tokio::fs::remove_dir_all;
 
 #[command]
 pub async fn create_instance(app: AppHandle, mut action: Action, context: Context) -> Result<Option<ActionInstance>, Error> {
@@ -17,9 +19,13 @@ pub async fn create_instance(app: AppHandle, mut action: Action, context: Contex
 	}
 
 	let mut locks = acquire_locks_mut().await;
+	let edit = PendingEdit::begin(&[&context], &mut locks).await?;
 	let slot = get_slot_mut(&context, &mut locks).await?;
 
 	if let Some(parent) = slot {
+		if matches!(action.uuid.as_str(), "opendeck.folder" | "opendeck.folderback") {
+			return Ok(None);
+		}
 		let Some(children) = &mut parent.children else { return Ok(None) };
 		let index = match children.last() {
 			None => 1,
@@ -33,6 +39,7 @@ pub async fn create_instance(app: AppHandle, mut action: Action, context: Contex
 			current_state: 0,
 			settings: serde_json::Value::Object(serde_json::Map::new()),
 			children: None,
+			page: None,
 		};
 		children.push(instance.clone());
 
@@ -42,8 +49,11 @@ pub async fn create_instance(app: AppHandle, mut action: Action, context: Contex
 				..Default::default()
 			});
 			let _ = update_state(&app, parent.context.clone(), &mut locks).await;
+		} else if parent.action.uuid == "opendeck.gestureaction" {
+			crate::events::outbound::gestures::bind_new_child(parent);
 		}
 
+		edit.commit(&mut locks).await?;
 		save_profile_now(&context.device, &mut locks).await?;
 		drop(locks);
 		let _ = crate::events::outbound::will_appear::will_appear(&instance).await;
@@ -52,22 +62,40 @@ pub async fn create_instance(app: AppHandle, mut action: Action, context: Contex
 		let slot = get_slot(&context, &locks).await?.clone();
 		Ok(slot)
 	} else {
-		let instance = ActionInstance {
+		let mut instance = ActionInstance {
 			action: action.clone(),
 			context: ActionContext::from_context(context.clone(), 0),
 			states: action.states.clone(),
 			current_state: 0,
 			settings: serde_json::Value::Object(serde_json::Map::new()),
-			children: if matches!(action.uuid.as_str(), "opendeck.multiaction" | "opendeck.toggleaction") {
+			children: if matches!(action.uuid.as_str(), "opendeck.multiaction" | "opendeck.toggleaction" | "opendeck.gestureaction") {
 				Some(vec![])
 			} else {
 				None
 			},
+			page: None,
 		};
+		if instance.action.uuid == "opendeck.folder" {
+			let device = crate::shared::DEVICES
+				.get(&context.device)
+				.map(|v| v.clone())
+				.ok_or_else(|| Error::new(format!("device {} not found", context.device)))?;
+			let back = crate::shared::CATEGORIES
+				.read()
+				.await
+				.values()
+				.flat_map(|v| v.actions.iter())
+				.find(|v| v.uuid == "opendeck.folderback")
+				.cloned()
+				.unwrap();
+			let id = format!("{}{PAGE_SEPARATOR}{}", context.profile, context.position);
+			instance.page = Some(Box::new(Profile::new_page(&device, id, back)));
+		}
 
 		*slot = Some(instance.clone());
 		let slot = slot.clone();
 
+		edit.commit(&mut locks).await?;
 		save_profile_now(&context.device, &mut locks).await?;
 		let _ = crate::events::outbound::will_appear::will_appear(&instance).await;
 
@@ -79,10 +107,28 @@ fn instance_images_dir(context: &ActionContext) -> std::path::PathBuf {
 	config_dir()
 		.join("images")
 		.join(&context.device)
-		.join(&context.profile)
+		.join(page_images_path(&context.profile))
 		.join(format!("{}.{}.{}", context.controller, context.position, context.index))
 }
 
+fn page_images_dir(device: &str, id: &str) -> std::path::PathBuf {
+	config_dir().join("images").join(device).join(page_images_path(id))
+}
+
+/// Update the paths of images within a folder page that has moved to a new image directory.
+fn rebase_images(page: &mut Profile, old_dir: &std::path::Path, new_dir: &std::path::Path) {
+	for instance in page.keys.iter_mut().chain(&mut page.sliders).chain(&mut page.infobars).flatten() {
+		for state in instance.states.iter_mut().chain(instance.children.iter_mut().flatten().flat_map(|child| child.states.iter_mut())) {
+			if let Ok(relative) = std::path::Path::new(&state.image).strip_prefix(old_dir) {
+				state.image = new_dir.join(relative).to_string_lossy().into_owned();
+			}
+		}
+		if let Some(page) = &mut instance.page {
+			rebase_images(page, old_dir, new_dir);
+		}
+	}
+}
+
 #[command]
 pub async fn move_instance(source: Context, destination: Context, retain: bool) -> Result<Option<ActionInstance>, Error> {
 	if source.controller != destination.controller {
@@ -98,6 +144,7 @@ pub async fn move_instance(source: Context, destination: Context, retain: bool)
 	}
 
 	let mut locks = acquire_locks_mut().await;
+	let mut edit = PendingEdit::begin(&[&source, &destination], &mut locks).await?;
 	let src = get_slot_mut(&source, &mut locks).await?;
 
 	let Some(mut new) = src.clone() else {
@@ -117,6 +164,16 @@ pub async fn move_instance(source: Context, destination: Context, retain: bool)
 		}
 	}
 
+	if let Some(page) = &mut new.page {
+		let old_dir = page_images_dir(&source.device, &page.id);
+		page.set_id(format!("{}{PAGE_SEPARATOR}{}", destination.profile, destination.position));
+		let new_dir = page_images_dir(&destination.device, &page.id);
+		if old_dir.exists() {
+			let _ = copy_dir(&old_dir, &new_dir);
+		}
+		rebase_images(page, &old_dir, &new_dir);
+	}
+
 	let old_dir = instance_images_dir(&src.as_ref().unwrap().context);
 	let new_dir = instance_images_dir(&new.context);
 	let _ = tokio::fs::create_dir_all(&new_dir).await;
@@ -139,13 +196,17 @@ pub async fn move_instance(source: Context, destination: Context, retain: bool)
 		let src = get_slot_mut(&source, &mut locks).await?;
 		if let Some(old) = src {
 			let _ = crate::events::outbound::will_appear::will_disappear(old, true).await;
-			let _ = remove_dir_all(instance_images_dir(&old.context)).await;
+			edit.discard_images(instance_images_dir(&old.context));
+			if let Some(page) = &old.page {
+				edit.discard_images(page_images_dir(&old.context.device, &page.id));
+			}
 		}
 		*src = None;
 	}
 
 	let _ = crate::events::outbound::will_appear::will_appear(&new).await;
 
+	edit.commit(&mut locks).await?;
 	save_profile_now(&destination.device, &mut locks).await?;
 
 	Ok(Some(new))
@@ -154,40 +215,55 @@ pub async fn move_instance(source: Context, destination: Context, retain: bool)
 #[command]
 pub async fn remove_instance(context: ActionContext) -> Result<(), Error> {
 	let mut locks = acquire_locks_mut().await;
-	let slot = get_slot_mut(&(&context).into(), &mut locks).await?;
+	let slot_context: Context = (&context).into();
+	let mut edit = PendingEdit::begin(&[&slot_context], &mut locks).await?;
+	let slot = get_slot_mut(&slot_context, &mut locks).await?;
 	let Some(instance) = slot else {
 		return Ok(());
 	};
 
 	if instance.context == context {
+		if instance.action.uuid == "opendeck.multiaction" {
+			crate::events::outbound::multi_action::cancel(&instance.context).await;
+		}
 		let _ = crate::events::outbound::will_appear::will_disappear(instance, true).await;
 		if let Some(children) = &instance.children {
 			for child in children {
 				let _ = crate::events::outbound::will_appear::will_disappear(child, true).await;
-				let _ = remove_dir_all(instance_images_dir(&child.context)).await;
+				edit.discard_images(instance_images_dir(&child.context));
 			}
 		}
-		let _ = remove_dir_all(instance_images_dir(&instance.context)).await;
+		edit.discard_images(instance_images_dir(&instance.context));
+		if let Some(page) = &instance.page {
+			edit.discard_images(page_images_dir(&instance.context.device, &page.id));
+		}
 		*slot = None;
 	} else {
 		let children = instance.children.as_mut().unwrap();
 		for (index, child) in children.iter().enumerate() {
 			if child.context == context {
 				let _ = crate::events::outbound::will_appear::will_disappear(child, true).await;
-				let _ = remove_dir_all(instance_images_dir(&child.context)).await;
+				edit.discard_images(instance_images_dir(&child.context));
 				children.remove(index);
 
 				if instance.action.uuid == "opendeck.multiaction"
 					&& let Some(settings) = instance.settings.as_object_mut()
-					&& let Some(delays) = settings.get_mut("delays").and_then(|v| v.as_array_mut())
 				{
-					if index == 0 {
-						if !delays.is_empty() {
-							delays.remove(0);
+					if let Some(delays) = settings.get_mut("delays").and_then(|v| v.as_array_mut()) {
+						if index == 0 {
+							if !delays.is_empty() {
+								delays.remove(0);
+							}
+						} else if index - 1 < delays.len() {
+							delays.remove(index - 1);
 						}
-					} else if index - 1 < delays.len() {
-						delays.remove(index - 1);
 					}
+					crate::events::outbound::multi_action::remove_child(settings, index);
+				} else if instance.action.uuid == "opendeck.gestureaction"
+					&& let Some(gestures) = instance.settings.get_mut("gestures").and_then(|v| v.as_array_mut())
+					&& index < gestures.len()
+				{
+					gestures.remove(index);
 				}
 
 				break;
@@ -204,6 +280,7 @@ pub async fn remove_instance(context: ActionContext) -> Result<(), Error> {
 		}
 	}
 
+	edit.commit(&mut locks).await?;
 	save_profile_now(&context.device, &mut locks).await?;
 
 	Ok(())
@@ -230,9 +307,11 @@ pub async fn update_state(app: &AppHandle, context: ActionContext, locks: &mut L
 #[command]
 pub async fn set_state(context: ActionContext, index: u16, state: ActionState) -> Result<(), Error> {
 	let mut locks = acquire_locks_mut().await;
+	let edit = PendingEdit::begin(&[&(&context).into()], &mut locks).await?.coalesce(format!("state.{context}.{index}"));
 	let reference = get_instance_mut(&context, &mut locks).await?.unwrap();
 	reference.states[index as usize] = state;
 	let clone = reference.clone();
+	edit.commit(&mut locks).await?;
 	save_profile_now(&context.device, &mut locks).await?;
 	crate::events::outbound::states::title_parameters_did_change(&clone, index).await?;
 	Ok(())
@@ -241,6 +320,7 @@ pub async fn set_state(context: ActionContext, index: u16, state: ActionState) -
 #[command]
 pub async fn set_child_delay(parent_context: ActionContext, index: usize, delay_ms: u64) -> Result<serde_json::Value, Error> {
 	let mut locks = acquire_locks_mut().await;
+	let edit = PendingEdit::begin(&[&(&parent_context).into()], &mut locks).await?.coalesce(format!("delay.{parent_context}.{index}"));
 	let Some(parent) = get_instance_mut(&parent_context, &mut locks).await? else {
 		return Ok(serde_json::Value::Null);
 	};
@@ -262,6 +342,99 @@ pub async fn set_child_delay(parent_context: ActionContext, index: usize, delay_
 	}
 	let parent_settings = parent.settings.clone();
 
+	edit.commit(&mut locks).await?;
+	save_profile_now(&parent_context.device, &mut locks).await?;
+	Ok(parent_settings)
+}
+
+#[command]
+pub async fn set_child_step(parent_context: ActionContext, index: usize, step: Step) -> Result<serde_json::Value, Error> {
+	let mut locks = acquire_locks_mut().await;
+	let edit = PendingEdit::begin(&[&(&parent_context).into()], &mut locks).await?;
+	let Some(parent) = get_instance_mut(&parent_context, &mut locks).await? else {
+		return Ok(serde_json::Value::Null);
+	};
+
+	if !parent.settings.is_object() {
+		parent.settings = serde_json::Value::Object(serde_json::Map::new());
+	}
+	let map = parent.settings.as_object_mut().unwrap();
+	let mut steps: Vec<Step> = map.get("steps").and_then(|v| serde_json::from_value(v.clone()).ok()).unwrap_or_default();
+	if steps.len() <= index {
+		steps.resize(index + 1, Step::default());
+	}
+	steps[index] = step;
+	map.insert("steps".to_owned(), serde_json::to_value(steps).unwrap());
+	let parent_settings = parent.settings.clone();
+
+	edit.commit(&mut locks).await?;
+	save_profile_now(&parent_context.device, &mut locks).await?;
+	Ok(parent_settings)
+}
+
+#[command]
+pub async fn set_multi_action_trigger(parent_context: ActionContext, trigger: Trigger) -> Result<serde_json::Value, Error> {
+	let mut locks = acquire_locks_mut().await;
+	let edit = PendingEdit::begin(&[&(&parent_context).into()], &mut locks).await?;
+	let Some(parent) = get_instance_mut(&parent_context, &mut locks).await? else {
+		return Ok(serde_json::Value::Null);
+	};
+
+	if !parent.settings.is_object() {
+		parent.settings = serde_json::Value::Object(serde_json::Map::new());
+	}
+	let map = parent.settings.as_object_mut().unwrap();
+	map.insert("trigger".to_owned(), serde_json::to_value(trigger).unwrap());
+	let parent_settings = parent.settings.clone();
+
+	edit.commit(&mut locks).await?;
+	save_profile_now(&parent_context.device, &mut locks).await?;
+	Ok(parent_settings)
+}
+
+#[command]
+pub async fn set_child_gesture(parent_context: ActionContext, index: usize, gesture: Gesture) -> Result<serde_json::Value, Error> {
+	let mut locks = acquire_locks_mut().await;
+	let edit = PendingEdit::begin(&[&(&parent_context).into()], &mut locks).await?;
+	let Some(parent) = get_instance_mut(&parent_context, &mut locks).await? else {
+		return Ok(serde_json::Value::Null);
+	};
+
+	if !parent.settings.is_object() {
+		parent.settings = serde_json::Value::Object(serde_json::Map::new());
+	}
+	let map = parent.settings.as_object_mut().unwrap();
+	let mut gestures: Vec<Gesture> = map.get("gestures").and_then(|v| serde_json::from_value(v.clone()).ok()).unwrap_or_default();
+	if gestures.len() <= index {
+		gestures.resize(index + 1, Gesture::Press);
+	}
+	gestures[index] = gesture;
+	map.insert("gestures".to_owned(), serde_json::to_value(gestures).unwrap());
+	let parent_settings = parent.settings.clone();
+
+	edit.commit(&mut locks).await?;
+	save_profile_now(&parent_context.device, &mut locks).await?;
+	Ok(parent_settings)
+}
+
+#[command]
+pub async fn set_gesture_thresholds(parent_context: ActionContext, long_press_ms: u64, double_tap_ms: u64, repeat_interval_ms: u64) -> Result<serde_json::Value, Error> {
+	let mut locks = acquire_locks_mut().await;
+	let edit = PendingEdit::begin(&[&(&parent_context).into()], &mut locks).await?.coalesce(format!("thresholds.{parent_context}"));
+	let Some(parent) = get_instance_mut(&parent_context, &mut locks).await? else {
+		return Ok(serde_json::Value::Null);
+	};
+
+	if !parent.settings.is_object() {
+		parent.settings = serde_json::Value::Object(serde_json::Map::new());
+	}
+	let map = parent.settings.as_object_mut().unwrap();
+	map.insert("long_press_ms".to_owned(), serde_json::json!(long_press_ms));
+	map.insert("double_tap_ms".to_owned(), serde_json::json!(double_tap_ms));
+	map.insert("repeat_interval_ms".to_owned(), serde_json::json!(repeat_interval_ms));
+	let parent_settings = parent.settings.clone();
+
+	edit.commit(&mut locks).await?;
 	save_profile_now(&parent_context.device, &mut locks).await?;
 	Ok(parent_settings)
 }
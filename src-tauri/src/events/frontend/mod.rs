@@ -1,8 +1,11 @@
+pub mod history;
 pub mod instances;
 pub mod plugins;
 pub mod profiles;
 pub mod property_inspector;
 pub mod settings;
+pub mod variables;
+pub mod virtual_devices;
 
 use crate::shared::{CATEGORIES, Category, DEVICES, DeviceInfo};
 
@@ -112,7 +115,26 @@ pub async fn get_application_profiles() -> crate::application_watcher::Applicati
 
 #[command]
 pub async fn set_application_profiles(value: crate::application_watcher::ApplicationProfiles) -> Result<(), Error> {
+	for rule in &value.rules {
+		rule.validate()?;
+	}
 	let mut store = crate::application_watcher::APPLICATION_PROFILES.write().await;
+	*crate::application_watcher::COMPILED_RULES.write().await = crate::application_watcher::compile_rules(&value.rules);
+	store.value = value;
+	Ok(store.save()?)
+}
+
+#[command]
+pub async fn get_schedules() -> crate::schedules::ScheduleRules {
+	crate::schedules::SCHEDULES.read().await.value.clone()
+}
+
+#[command]
+pub async fn set_schedules(value: crate::schedules::ScheduleRules) -> Result<(), Error> {
+	for rule in &value {
+		rule.validate()?;
+	}
+	let mut store = crate::schedules::SCHEDULES.write().await;
 	store.value = value;
 	Ok(store.save()?)
 }
@@ -121,3 +143,8 @@ pub async fn set_application_profiles(value: crate::application_watcher::Applica
 pub fn get_fonts() -> Vec<String> {
 	system_fonts::query_all()
 }
+
+#[command]
+pub fn get_image_cache_stats() -> crate::image_cache::CacheStats {
+	crate::image_cache::stats()
+}
@@ -1,9 +1,11 @@
 use super::Error;
 
+use crate::plugins::validation::{Diagnostic, cached_diagnostics, invalidate_diagnostics};
 use crate::plugins::{SpawnRequest, deactivate_plugin, initialise_plugin};
 use crate::shared::{config_dir, log_dir};
 use crate::store::profiles::{acquire_locks, get_instance};
 
+use std::collections::HashMap;
 use std::sync::mpsc;
 
 use tauri::{AppHandle, Emitter, Manager, command};
@@ -19,6 +21,7 @@ pub struct PluginInfo {
 	has_settings_interface: bool,
 	builtin: bool,
 	registered: bool,
+	diagnostics: Vec<Diagnostic>,
 }
 
 #[command]
@@ -55,6 +58,7 @@ pub async fn list_plugins(app: AppHandle) -> Result<Vec<PluginInfo>, Error> {
 				has_settings_interface: manifest.has_settings_interface.unwrap_or(false),
 				builtin: builtins.contains(&id),
 				registered: registered.contains(&id),
+				diagnostics: cached_diagnostics(&id, &path),
 				id,
 			});
 		}
@@ -64,7 +68,8 @@ pub async fn list_plugins(app: AppHandle) -> Result<Vec<PluginInfo>, Error> {
 }
 
 #[command]
-pub async fn install_plugin(app: AppHandle, url: Option<String>, file: Option<String>, fallback_id: Option<String>) -> Result<(), Error> {
+pub async fn install_plugin(app: AppHandle, url: Option<String>, file: Option<String>, fallback_id: Option<String>, sha256: Option<String>, signature: Option<String>) -> Result<(), Error> {
+	let source = url.clone().or_else(|| file.clone());
 	let bytes = match file {
 		None => {
 			let resp = match reqwest::get(url.unwrap()).await {
@@ -83,6 +88,8 @@ pub async fn install_plugin(app: AppHandle, url: Option<String>, file: Option<St
 		},
 	};
 
+	let record = crate::plugins::integrity::verify(&bytes, sha256.as_deref(), signature.as_deref())?;
+
 	let id = match crate::zip_extract::dir_name(std::io::Cursor::new(&bytes)) {
 		Ok(id) => {
 			log::trace!("Found directory with name {id} within archive");
@@ -105,6 +112,7 @@ pub async fn install_plugin(app: AppHandle, url: Option<String>, file: Option<St
 	let temp = config_dir.join("temp").join(&id);
 	let _ = fs::rename(&actual, &temp).await;
 
+	invalidate_diagnostics(&id);
 	let tx = (*app.state::<mpsc::Sender<SpawnRequest>>()).clone();
 	if let Err(error) = crate::zip_extract::extract(std::io::Cursor::new(bytes), &config_dir.join("plugins")) {
 		log::error!("Failed to unzip file: {}", error);
@@ -120,6 +128,9 @@ pub async fn install_plugin(app: AppHandle, url: Option<String>, file: Option<St
 		return Err(error.into());
 	}
 	let _ = fs::remove_dir_all(config_dir.join("temp")).await;
+	if let Err(error) = crate::plugins::integrity::save_record(&id, record, source) {
+		log::warn!("Failed to save install record for plugin {id}: {error:#}");
+	}
 
 	use tauri_plugin_aptabase::EventTracker;
 	let _ = app.track_event("plugin_installed", Some(serde_json::json!({ "id": id.strip_suffix(".sdPlugin").unwrap_or(&id) })));
@@ -148,14 +159,19 @@ pub async fn remove_plugin(app: AppHandle, id: String) -> Result<(), Error> {
 	}
 	categories.retain(|_, v| !v.actions.is_empty());
 
+	crate::events::clear_plugin_queue(&id).await;
 	let _ = fs::remove_file(log_dir().join("plugins").join(format!("{id}.log"))).await;
 	let _ = fs::remove_file(config_dir().join("settings").join(format!("{id}.json"))).await;
+	crate::plugins::integrity::remove_record(&id);
+	invalidate_diagnostics(&id);
 
 	Ok(())
 }
 
 #[command]
 pub async fn reload_plugin(app: AppHandle, id: String) {
+	crate::plugins::supervisor::reset(&id);
+	invalidate_diagnostics(&id);
 	let _ = deactivate_plugin(&app, &id).await;
 	let tx = (*app.state::<mpsc::Sender<SpawnRequest>>()).clone();
 	let _ = initialise_plugin(config_dir().join("plugins").join(&id), tx).await;
@@ -174,6 +190,21 @@ pub async fn reload_plugin(app: AppHandle, id: String) {
 	}
 }
 
+#[command]
+pub async fn validate_plugin_manifest(id: String) -> Result<Vec<Diagnostic>, Error> {
+	let path = config_dir().join("plugins").join(&id);
+	if !path.is_dir() {
+		return Err(Error::new(format!("plugin {id} not found")));
+	}
+	invalidate_diagnostics(&id);
+	Ok(cached_diagnostics(&id, &path))
+}
+
+#[command]
+pub async fn get_plugin_queue_depths() -> HashMap<String, usize> {
+	crate::events::plugin_queue_depths().await
+}
+
 #[command]
 pub async fn show_settings_interface(plugin: String) -> Result<(), Error> {
 	crate::events::outbound::settings::show_settings_interface(&plugin).await?;
@@ -1,9 +1,11 @@
 use super::Error;
 
-use crate::shared::DEVICES;
+use crate::shared::{CATEGORIES, DEVICES, PAGE_SEPARATOR, initialise_encoder_layout, page_root};
 use crate::store::profiles::{PROFILE_STORES, acquire_locks_mut, get_device_profiles, save_profile_now};
+use crate::store::streamdeck_profile::{self, ImportReport};
 
 use tauri::{AppHandle, Emitter, Manager, command};
+use tauri_plugin_dialog::{DialogExt, FilePath};
 
 #[command]
 pub fn get_profiles(device: &str) -> Result<Vec<String>, Error> {
@@ -18,9 +20,9 @@ pub async fn get_selected_profile(device: String) -> Result<crate::shared::Profi
 	}
 
 	let selected_profile = locks.device_stores.get_selected_profile(&device)?;
-	let profile = locks.profile_stores.get_profile_store(&DEVICES.get(&device).unwrap(), &selected_profile)?;
+	let profile = locks.profile_stores.get_page(&DEVICES.get(&device).unwrap(), &selected_profile)?;
 
-	Ok(profile.value.clone())
+	Ok(profile.clone())
 }
 
 #[allow(clippy::flat_map_identity)]
@@ -36,10 +38,16 @@ pub async fn set_selected_profile(device: String, id: String) -> Result<(), Erro
 		log::error!("Failed to save profile for device {device}: {error}");
 	}
 
+	// Only profiles that already exist may have their folder pages selected, as new profiles cannot contain the separator.
+	if id.contains(PAGE_SEPARATOR) && !get_device_profiles(&device)?.iter().any(|profile| profile == page_root(&id)) {
+		return Err(Error::new(format!("profile names cannot contain \"{PAGE_SEPARATOR}\"")));
+	}
+
 	let selected_profile = locks.device_stores.get_selected_profile(&device)?;
 
 	if selected_profile != id {
-		let old_profile = &locks.profile_stores.get_profile_store(&DEVICES.get(&device).unwrap(), &selected_profile)?.value;
+		crate::events::outbound::multi_action::cancel_device(&device);
+		let old_profile = locks.profile_stores.get_page(&DEVICES.get(&device).unwrap(), &selected_profile)?;
 		for instance in old_profile
 			.keys
 			.iter()
@@ -47,7 +55,7 @@ pub async fn set_selected_profile(device: String, id: String) -> Result<(), Erro
 			.chain(&mut old_profile.sliders.iter().flatten())
 			.chain(&mut old_profile.infobars.iter().flatten())
 		{
-			if !matches!(instance.action.uuid.as_str(), "opendeck.multiaction" | "opendeck.toggleaction") {
+			if !matches!(instance.action.uuid.as_str(), "opendeck.multiaction" | "opendeck.toggleaction" | "opendeck.gestureaction") {
 				let _ = crate::events::outbound::will_appear::will_disappear(instance, false).await;
 			} else {
 				for child in instance.children.as_ref().unwrap() {
@@ -58,9 +66,8 @@ pub async fn set_selected_profile(device: String, id: String) -> Result<(), Erro
 		let _ = crate::events::outbound::devices::clear_screen(device.clone()).await;
 	}
 
-	// We must use the mutable version of get_profile_store in order to create the store if it does not exist.
-	let store = locks.profile_stores.get_profile_store_mut(&DEVICES.get(&device).unwrap(), &id).await?;
-	let new_profile = &store.value;
+	// We must use the mutable version of get_page in order to create the store if it does not exist.
+	let new_profile = locks.profile_stores.get_page_mut(&DEVICES.get(&device).unwrap(), &id).await?;
 	for instance in new_profile
 		.keys
 		.iter()
@@ -68,7 +75,7 @@ pub async fn set_selected_profile(device: String, id: String) -> Result<(), Erro
 		.chain(&mut new_profile.sliders.iter().flatten())
 		.chain(&mut new_profile.infobars.iter().flatten())
 	{
-		if !matches!(instance.action.uuid.as_str(), "opendeck.multiaction" | "opendeck.toggleaction") {
+		if !matches!(instance.action.uuid.as_str(), "opendeck.multiaction" | "opendeck.toggleaction" | "opendeck.gestureaction") {
 			let _ = crate::events::outbound::will_appear::will_appear(instance).await;
 		} else {
 			for child in instance.children.as_ref().unwrap() {
@@ -76,8 +83,9 @@ pub async fn set_selected_profile(device: String, id: String) -> Result<(), Erro
 			}
 		}
 	}
-	store.save()?;
+	locks.profile_stores.get_profile_store_mut(&DEVICES.get(&device).unwrap(), page_root(&id)).await?.save()?;
 
+	crate::mqtt::publish_profile(&device, page_root(&id));
 	locks.device_stores.set_selected_profile(&device, id)?;
 
 	Ok(())
@@ -87,6 +95,11 @@ pub async fn set_selected_profile(device: String, id: String) -> Result<(), Erro
 pub async fn delete_profile(device: String, profile: String) {
 	let mut profile_stores = PROFILE_STORES.write().await;
 	profile_stores.delete_profile(&device, &profile);
+	drop(profile_stores);
+	super::history::forget(&device, &profile);
+	if let Err(error) = crate::variables::forget(&device, Some(&profile)).await {
+		log::warn!("Failed to remove variables of deleted profile {profile}: {error:#}");
+	}
 }
 
 #[command]
@@ -97,6 +110,11 @@ pub async fn rename_profile(device: String, old_id: String, new_id: String, reta
 	}
 
 	locks.profile_stores.rename_profile(&DEVICES.get(&device).unwrap(), &old_id, &new_id, retain).await?;
+	if !retain {
+		super::history::forget(&device, &old_id);
+	}
+	drop(locks);
+	crate::variables::rename(&device, &old_id, &new_id, retain).await?;
 
 	Ok(())
 }
@@ -106,3 +124,62 @@ pub async fn rerender_images(app: &AppHandle) -> Result<(), anyhow::Error> {
 	window.emit("rerender_images", ())?;
 	Ok(())
 }
+
+#[command]
+pub async fn import_streamdeck_profile(app: AppHandle, device: String) -> Result<Option<ImportReport>, Error> {
+	let Some(device) = DEVICES.get(&device).map(|v| v.clone()) else {
+		return Err(Error::new(format!("device {device} not found")));
+	};
+
+	let path = app
+		.dialog()
+		.file()
+		.add_filter("Stream Deck profile", &["streamDeckProfile", "streamDeckProfilesBackup"])
+		.blocking_pick_file();
+	let Some(FilePath::Path(path)) = path else {
+		return Ok(None);
+	};
+
+	let actions = CATEGORIES.read().await.values().flat_map(|category| category.actions.clone()).collect::<Vec<_>>();
+	let (profiles, report) = streamdeck_profile::import(&std::fs::read(path)?, &device, &actions, get_device_profiles(&device.id)?)?;
+
+	let mut locks = acquire_locks_mut().await;
+	for mut profile in profiles {
+		for instance in profile.sliders.iter_mut().flatten() {
+			let _ = initialise_encoder_layout(&mut instance.action, None);
+		}
+		let store = locks.profile_stores.get_profile_store_mut(&device, &profile.id).await?;
+		store.value = profile;
+		store.save()?;
+	}
+
+	Ok(Some(report))
+}
+
+#[command]
+pub async fn export_streamdeck_profile(app: AppHandle, device: String, profile: String) -> Result<bool, Error> {
+	let Some(device) = DEVICES.get(&device).map(|v| v.clone()) else {
+		return Err(Error::new(format!("device {device} not found")));
+	};
+
+	let kind = crate::elgato::device_kind(&device.id).await;
+	let bytes = {
+		let mut locks = acquire_locks_mut().await;
+		let store = locks.profile_stores.get_profile_store_mut(&device, &profile).await?;
+		streamdeck_profile::export(&store.value, &device, kind)?
+	};
+
+	let name = profile.rsplit('/').next().unwrap_or(&profile);
+	let path = app
+		.dialog()
+		.file()
+		.set_file_name(format!("{name}.streamDeckProfile"))
+		.add_filter("Stream Deck profile", &["streamDeckProfile"])
+		.blocking_save_file();
+	let Some(FilePath::Path(path)) = path else {
+		return Ok(false);
+	};
+	std::fs::write(path, bytes)?;
+
+	Ok(true)
+}
@@ -7,7 +7,7 @@ use tauri::command;
 #[command]
 pub async fn make_info(plugin: String) -> Result<crate::plugins::info_param::Info, Error> {
 	let manifest = crate::plugins::manifest::read_manifest(&crate::shared::config_dir().join("plugins").join(&plugin))?;
-	Ok(crate::plugins::info_param::make_info(plugin, manifest.version, false).await)
+	Ok(crate::plugins::info_param::make_info(plugin, manifest.version, false, None).await)
 }
 
 #[command]
@@ -30,6 +30,9 @@ pub async fn set_settings(_app: AppHandle, settings: crate::store::Settings) ->
 	crate::events::outbound::devices::set_brightness(settings.brightness).await?;
 	crate::device_sleep::update_sleep_timeout_minutes(settings.sleep_timeout_minutes).await?;
 	crate::device_sleep::update_sleep_when_computer_locked(settings.sleep_when_computer_locked).await?;
+	crate::http_api::update_api(&settings);
+	crate::mqtt::update_mqtt(&settings);
+	crate::plugins::hot_reload::update_hot_reload(&settings);
 
 	let mut store = crate::store::SETTINGS_MUT.lock().await;
 	store.value = settings;
@@ -37,6 +40,31 @@ pub async fn set_settings(_app: AppHandle, settings: crate::store::Settings) ->
 	Ok(())
 }
 
+#[command]
+pub fn generate_api_token() -> String {
+	crate::http_api::generate_token()
+}
+
+#[command]
+pub async fn get_device_settings(device: String) -> Result<crate::store::profiles::DeviceSettings, Error> {
+	Ok(crate::store::profiles::DEVICE_SETTINGS.write().await.get(&device)?.clone())
+}
+
+#[command]
+pub async fn set_device_settings(device: String, settings: crate::store::profiles::DeviceSettings) -> Result<(), Error> {
+	let brightness = settings.brightness();
+	crate::store::profiles::DEVICE_SETTINGS.write().await.set(&device, settings)?;
+
+	if crate::shared::DEVICES.contains_key(&device) {
+		// Sleeping devices stay dark, and are set to the new brightness when they wake.
+		if !crate::device_sleep::is_device_sleeping(&device) {
+			crate::events::outbound::devices::set_device_brightness(&device, brightness).await?;
+		}
+		crate::device_sleep::wake_devices_without_timeout().await?;
+	}
+	Ok(())
+}
+
 #[command]
 pub fn open_config_directory() -> Result<(), Error> {
 	if let Err(error) = open::that_detached(config_dir()) {
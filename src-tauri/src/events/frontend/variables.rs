@@ -0,0 +1,22 @@
+use super::Error;
+
+use crate::shared::ActionContext;
+use crate::variables::{VARIABLES, Variables};
+
+use tauri::command;
+
+#[command]
+pub async fn get_variables() -> Variables {
+	VARIABLES.read().await.value.clone()
+}
+
+#[command]
+pub async fn set_variable(name: String, value: Option<String>, device: Option<String>, profile: Option<String>) -> Result<(), Error> {
+	Ok(crate::variables::set_variable(&name, value, device.as_deref(), profile.as_deref()).await?)
+}
+
+/// Resolve the placeholders in the title of an instance, as the frontend does before rendering it.
+#[command]
+pub async fn resolve_title(context: ActionContext, text: String) -> String {
+	crate::variables::resolve(&text, &context.device, &context.profile).await
+}
@@ -0,0 +1,35 @@
+use super::Error;
+
+use crate::virtual_device::{VIRTUAL_DEVICES, VirtualDeviceState, VirtualDevices, VirtualInputEvent};
+
+use tauri::command;
+
+#[command]
+pub async fn get_virtual_devices() -> VirtualDevices {
+	VIRTUAL_DEVICES.read().await.value.clone()
+}
+
+#[command]
+pub async fn set_virtual_devices(mut value: VirtualDevices) -> Result<(), Error> {
+	{
+		let mut store = VIRTUAL_DEVICES.write().await;
+		if value.socket_token.is_empty() {
+			value.socket_token = std::mem::take(&mut store.value.socket_token);
+		}
+		store.value = value;
+		store.save()?;
+	}
+	crate::virtual_device::sync_virtual_devices().await?;
+	crate::virtual_device::update_socket().await?;
+	Ok(())
+}
+
+#[command]
+pub async fn get_virtual_device_state(device: String) -> Option<VirtualDeviceState> {
+	crate::virtual_device::get_state(&device)
+}
+
+#[command]
+pub async fn send_virtual_device_input(event: VirtualInputEvent) -> Result<Option<VirtualDeviceState>, Error> {
+	Ok(crate::virtual_device::process_input(event).await?)
+}
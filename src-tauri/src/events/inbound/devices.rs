@@ -5,9 +5,11 @@ use crate::shared::DEVICES;
 use crate::store::profiles::get_device_profiles;
 
 use serde::Deserialize;
+use serde_json::json;
 
 pub async fn register_device(uuid: &str, mut event: PayloadEvent<crate::shared::DeviceInfo>) -> Result<(), anyhow::Error> {
 	if uuid.is_empty() || Some(uuid) == DEVICE_NAMESPACES.read().await.get(&event.payload.id[..2]).map(|x| x.as_str()) {
+		crate::store::profiles::rename_legacy_profiles(&event.payload).await;
 		if let Ok(profiles) = get_device_profiles(&event.payload.id) {
 			let mut profile_stores = crate::store::profiles::PROFILE_STORES.write().await;
 			for profile in profiles {
@@ -21,20 +23,17 @@ pub async fn register_device(uuid: &str, mut event: PayloadEvent<crate::shared::
 		event.payload.plugin = uuid.to_owned();
 		let _ = crate::events::outbound::devices::device_did_connect(&event.payload.id, (&event.payload).into()).await;
 		DEVICES.insert(event.payload.id.clone(), event.payload.clone());
+		let brightness = crate::store::profiles::get_device_settings(&event.payload.id).await.brightness();
+		let _ = crate::events::outbound::devices::set_device_brightness(&event.payload.id, brightness).await;
 		let _ = crate::device_sleep::apply_initial_device_sleep(&event.payload.id).await;
 		crate::events::frontend::update_devices().await;
 
 		let mut locks = crate::store::profiles::acquire_locks_mut().await;
 		let selected_profile = locks.device_stores.get_selected_profile(&event.payload.id)?;
-		let profile = locks.profile_stores.get_profile_store(&DEVICES.get(&event.payload.id).unwrap(), &selected_profile)?;
-		for instance in profile
-			.value
-			.keys
-			.iter()
-			.flatten()
-			.chain(profile.value.sliders.iter().flatten())
-			.chain(profile.value.infobars.iter().flatten())
-		{
+		crate::mqtt::publish_device_connected(&event.payload);
+		crate::mqtt::publish_profile(&event.payload.id, &selected_profile);
+		let profile = locks.profile_stores.get_page(&DEVICES.get(&event.payload.id).unwrap(), &selected_profile)?;
+		for instance in profile.keys.iter().flatten().chain(profile.sliders.iter().flatten()).chain(profile.infobars.iter().flatten()) {
 			let _ = crate::events::outbound::will_appear::will_appear(instance).await;
 		}
 
@@ -56,18 +55,13 @@ pub async fn deregister_device(uuid: &str, event: PayloadEvent<String>) -> Resul
 			return Ok(());
 		}
 
+		crate::events::outbound::multi_action::cancel_device(&event.payload);
+		crate::events::outbound::multi_action::release_device(&event.payload).await;
 		let mut locks = crate::store::profiles::acquire_locks_mut().await;
 
 		let selected_profile = locks.device_stores.get_selected_profile(&event.payload)?;
-		let profile = locks.profile_stores.get_profile_store(&DEVICES.get(&event.payload).unwrap(), &selected_profile)?;
-		for instance in profile
-			.value
-			.keys
-			.iter()
-			.flatten()
-			.chain(profile.value.sliders.iter().flatten())
-			.chain(profile.value.infobars.iter().flatten())
-		{
+		let profile = locks.profile_stores.get_page(&DEVICES.get(&event.payload).unwrap(), &selected_profile)?;
+		for instance in profile.keys.iter().flatten().chain(profile.sliders.iter().flatten()).chain(profile.infobars.iter().flatten()) {
 			let _ = crate::events::outbound::will_appear::will_disappear(instance, false).await;
 		}
 
@@ -87,6 +81,7 @@ pub async fn deregister_device(uuid: &str, event: PayloadEvent<String>) -> Resul
 		let _ = crate::events::outbound::devices::device_did_disconnect(&event.payload).await;
 		DEVICES.remove(&event.payload);
 		crate::device_sleep::deregister_device(&event.payload);
+		crate::mqtt::publish_device_disconnected(&event.payload);
 		crate::events::frontend::update_devices().await;
 
 		Ok(())
@@ -105,6 +100,7 @@ pub async fn key_down(event: PayloadEvent<PressPayload>) -> Result<(), anyhow::E
 	if crate::device_sleep::note_activity(&event.payload.device).await.unwrap_or(false) {
 		return Ok(());
 	}
+	crate::mqtt::publish_input(&event.payload.device, "key", event.payload.position, json!({ "event": "keyDown" }));
 	crate::events::outbound::keypad::key_down(&event.payload.device, event.payload.position).await
 }
 
@@ -112,6 +108,7 @@ pub async fn key_up(event: PayloadEvent<PressPayload>) -> Result<(), anyhow::Err
 	if crate::device_sleep::note_activity(&event.payload.device).await.unwrap_or(false) {
 		return Ok(());
 	}
+	crate::mqtt::publish_input(&event.payload.device, "key", event.payload.position, json!({ "event": "keyUp" }));
 	crate::events::outbound::keypad::key_up(&event.payload.device, event.payload.position).await
 }
 
@@ -126,6 +123,7 @@ pub async fn encoder_change(event: PayloadEvent<TicksPayload>) -> Result<(), any
 	if crate::device_sleep::note_activity(&event.payload.device).await.unwrap_or(false) {
 		return Ok(());
 	}
+	crate::mqtt::publish_input(&event.payload.device, "dial", event.payload.position, json!({ "event": "dialRotate", "ticks": event.payload.ticks }));
 	crate::events::outbound::encoder::dial_rotate(&event.payload.device, event.payload.position, event.payload.ticks).await
 }
 
@@ -133,6 +131,7 @@ pub async fn encoder_down(event: PayloadEvent<PressPayload>) -> Result<(), anyho
 	if crate::device_sleep::note_activity(&event.payload.device).await.unwrap_or(false) {
 		return Ok(());
 	}
+	crate::mqtt::publish_input(&event.payload.device, "dial", event.payload.position, json!({ "event": "dialDown" }));
 	crate::events::outbound::encoder::dial_press(&event.payload.device, "dialDown", event.payload.position).await
 }
 
@@ -140,6 +139,7 @@ pub async fn encoder_up(event: PayloadEvent<PressPayload>) -> Result<(), anyhow:
 	if crate::device_sleep::note_activity(&event.payload.device).await.unwrap_or(false) {
 		return Ok(());
 	}
+	crate::mqtt::publish_input(&event.payload.device, "dial", event.payload.position, json!({ "event": "dialUp" }));
 	crate::events::outbound::encoder::dial_press(&event.payload.device, "dialUp", event.payload.position).await
 }
 
@@ -157,6 +157,12 @@ pub async fn touchscreen_press(event: PayloadEvent<TouchscreenPressPayload>) ->
 	if crate::device_sleep::note_activity(&event.payload.device).await.unwrap_or(false) {
 		return Ok(());
 	}
+	crate::mqtt::publish_input(
+		&event.payload.device,
+		"touch",
+		event.payload.position,
+		json!({ "event": "touchTap", "x": event.payload.x, "y": event.payload.y, "hold": event.payload.hold }),
+	);
 	crate::events::outbound::encoder::touch_tap(&event.payload.device, event.payload.position, event.payload.x, event.payload.y, event.payload.hold).await
 }
 
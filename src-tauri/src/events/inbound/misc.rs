@@ -42,10 +42,24 @@ pub async fn show_ok(event: ContextEvent) -> Result<(), anyhow::Error> {
 	Ok(())
 }
 
+#[derive(Deserialize)]
+pub struct SetVariableEvent {
+	pub name: String,
+	/// The new value of the variable, or `None` to remove it.
+	pub value: Option<String>,
+	pub device: Option<String>,
+	pub profile: Option<String>,
+}
+
+pub async fn set_variable(event: PayloadEvent<SetVariableEvent>) -> Result<(), anyhow::Error> {
+	let SetVariableEvent { name, value, device, profile } = event.payload;
+	crate::variables::set_variable(&name, value, device.as_deref(), profile.as_deref()).await
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct SwitchProfileEvent {
-	device: String,
-	profile: String,
+	pub device: String,
+	pub profile: String,
 }
 
 pub async fn switch_profile(event: SwitchProfileEvent) -> Result<(), anyhow::Error> {
@@ -1,8 +1,8 @@
 pub(crate) mod devices;
-mod misc;
+pub(crate) mod misc;
 mod property_inspector;
 mod settings;
-mod states;
+pub(crate) mod states;
 
 use crate::{
 	shared::ActionContext,
@@ -18,7 +18,7 @@ use serde::Deserialize;
 #[serde(tag = "event")]
 #[serde(rename_all = "camelCase")]
 pub enum RegisterEvent {
-	RegisterPlugin { uuid: String },
+	RegisterPlugin { uuid: String, token: Option<String> },
 	RegisterPropertyInspector { uuid: String },
 }
 
@@ -68,6 +68,7 @@ pub enum InboundEventType {
 	SendToPlugin(ContextAndPayloadEvent<serde_json::Value>),
 	SwitchProfile(misc::SwitchProfileEvent),
 	DeviceBrightness(misc::DeviceBrightnessEvent),
+	SetVariable(PayloadEvent<misc::SetVariableEvent>),
 }
 
 pub async fn process_incoming_message(data: Result<Message, Error>, uuid: &str, skip_auth: bool) {
@@ -146,6 +147,7 @@ pub async fn process_incoming_message(data: Result<Message, Error>, uuid: &str,
 			InboundEventType::SendToPlugin(_) => Ok(()),
 			InboundEventType::SwitchProfile(event) => misc::switch_profile(event).await,
 			InboundEventType::DeviceBrightness(event) => misc::device_brightness(event).await,
+			InboundEventType::SetVariable(event) => misc::set_variable(event).await,
 		} && !error.to_string().contains("closed connection")
 		{
 			warn!("Failed to process incoming event from plugin: {}", error);
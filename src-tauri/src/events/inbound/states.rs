@@ -9,14 +9,14 @@ use serde_json::Value;
 
 #[derive(Deserialize)]
 pub struct SetTitlePayload {
-	title: Option<String>,
-	state: Option<u16>,
+	pub title: Option<String>,
+	pub state: Option<u16>,
 }
 
 #[derive(Deserialize)]
 pub struct SetImagePayload {
-	image: Option<String>,
-	state: Option<u16>,
+	pub image: Option<String>,
+	pub state: Option<u16>,
 }
 
 #[derive(Deserialize)]
@@ -1,8 +1,10 @@
 pub mod frontend;
 pub mod inbound;
 pub mod outbound;
+mod queue;
 
 use inbound::RegisterEvent;
+use queue::MessageQueue;
 
 use std::collections::HashMap;
 use std::sync::LazyLock;
@@ -15,26 +17,82 @@ use tokio_tungstenite::{WebSocketStream, tungstenite::Message};
 type Sockets = LazyLock<Mutex<HashMap<String, SplitSink<WebSocketStream<TcpStream>, Message>>>>;
 static PLUGIN_SOCKETS: Sockets = LazyLock::new(|| Mutex::new(HashMap::new()));
 static PROPERTY_INSPECTOR_SOCKETS: Sockets = LazyLock::new(|| Mutex::new(HashMap::new()));
-static PLUGIN_QUEUES: LazyLock<RwLock<HashMap<String, Vec<Message>>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
-static PROPERTY_INSPECTOR_QUEUES: LazyLock<RwLock<HashMap<String, Vec<Message>>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+static PLUGIN_QUEUES: LazyLock<RwLock<HashMap<String, MessageQueue>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+static PROPERTY_INSPECTOR_QUEUES: LazyLock<RwLock<HashMap<String, MessageQueue>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
 
 pub async fn registered_plugins() -> Vec<String> {
 	PLUGIN_SOCKETS.lock().await.keys().map(|x| x.to_owned()).collect()
 }
 
+/// Get the number of messages waiting to be delivered to each plugin that is not connected.
+pub async fn plugin_queue_depths() -> HashMap<String, usize> {
+	let queues = PLUGIN_QUEUES.read().await;
+	queues.iter().map(|(plugin, queue)| (plugin.clone(), queue.len())).filter(|(_, len)| *len != 0).collect()
+}
+
+/// Queue a message for a plugin that is not connected, discarding expired messages from all queues.
+async fn enqueue_plugin_message(plugin: &str, message: Message, value: &serde_json::Value) {
+	let mut queues = PLUGIN_QUEUES.write().await;
+	queues.values_mut().for_each(MessageQueue::prune);
+	queues.retain(|_, queue| !queue.is_empty());
+	queues.entry(plugin.to_owned()).or_default().push(message, value);
+}
+
+/// Discard all messages waiting to be delivered to a plugin.
+pub async fn clear_plugin_queue(uuid: &str) {
+	PLUGIN_QUEUES.write().await.remove(uuid);
+}
+
+/// Check a plugin registration against the token issued to the plugin when it was launched.
+async fn authenticate_plugin(uuid: &str, token: Option<&str>) -> bool {
+	let expected = crate::plugins::expected_token(uuid);
+	let connected = PLUGIN_SOCKETS.lock().await.contains_key(uuid);
+	accepts_registration(expected.as_deref(), token, &crate::store::get_settings().value, connected)
+}
+
+/// Decide whether to accept a registration of a plugin that was issued the `expected` token, if it was launched by OpenDeck.
+///
+/// The token is only passed to plugins in the info parameter, which the Elgato and OpenAction SDKs do not send back when registering,
+/// so registrations without a token are accepted while no other connection holds the plugin's UUID, unless tokens are required.
+fn accepts_registration(expected: Option<&str>, token: Option<&str>, settings: &crate::store::Settings, connected: bool) -> bool {
+	let Some(expected) = expected else {
+		return false;
+	};
+	match token {
+		Some(token) => token == expected,
+		None => !settings.require_plugin_tokens && !connected,
+	}
+}
+
+/// Check that a property inspector registration refers to an existing action instance.
+async fn authenticate_property_inspector(context: &str) -> bool {
+	let Ok(context) = context.parse::<crate::shared::ActionContext>() else {
+		return false;
+	};
+	matches!(crate::store::profiles::get_instance(&context, &crate::store::profiles::acquire_locks().await).await, Ok(Some(_)))
+}
+
 /// Register a plugin or property inspector to send and receive events with its WebSocket.
 pub async fn register_plugin(event: RegisterEvent, stream: WebSocketStream<TcpStream>) {
 	let (mut read, write) = stream.split();
 	match event {
-		RegisterEvent::RegisterPlugin { uuid } => {
+		RegisterEvent::RegisterPlugin { uuid, token } => {
+			if !authenticate_plugin(&uuid, token.as_deref()).await {
+				log::warn!("Rejected registration of plugin {} with a missing or invalid token", uuid);
+				let _ = read.close().await;
+				return;
+			}
 			log::debug!("Registered plugin {}", uuid);
-			if let Some(queue) = PLUGIN_QUEUES.read().await.get(&uuid) {
-				for message in queue {
-					let _ = read.feed(message.clone()).await;
+			// Hold the socket lock while replaying so that no messages are queued after the queue has been taken.
+			let mut sockets = PLUGIN_SOCKETS.lock().await;
+			if let Some(queue) = PLUGIN_QUEUES.write().await.remove(&uuid) {
+				for message in queue.into_messages() {
+					let _ = read.feed(message).await;
 				}
 				let _ = read.flush().await;
 			}
-			PLUGIN_SOCKETS.lock().await.insert(uuid.clone(), read);
+			sockets.insert(uuid.clone(), read);
+			drop(sockets);
 			tokio::spawn(async move {
 				let uuid = uuid;
 				write.for_each(|event| inbound::process_incoming_message(event, &uuid, false)).await;
@@ -42,13 +100,20 @@ pub async fn register_plugin(event: RegisterEvent, stream: WebSocketStream<TcpSt
 			});
 		}
 		RegisterEvent::RegisterPropertyInspector { uuid } => {
-			if let Some(queue) = PROPERTY_INSPECTOR_QUEUES.read().await.get(&uuid) {
-				for message in queue {
-					let _ = read.feed(message.clone()).await;
+			if !authenticate_property_inspector(&uuid).await {
+				log::warn!("Rejected registration of property inspector for unknown context {}", uuid);
+				let _ = read.close().await;
+				return;
+			}
+			let mut sockets = PROPERTY_INSPECTOR_SOCKETS.lock().await;
+			if let Some(queue) = PROPERTY_INSPECTOR_QUEUES.write().await.remove(&uuid) {
+				for message in queue.into_messages() {
+					let _ = read.feed(message).await;
 				}
 				let _ = read.flush().await;
 			}
-			PROPERTY_INSPECTOR_SOCKETS.lock().await.insert(uuid.clone(), read);
+			sockets.insert(uuid.clone(), read);
+			drop(sockets);
 			tokio::spawn(async move {
 				let uuid = uuid;
 				write.for_each(|event| inbound::process_incoming_message_pi(event, &uuid)).await;
@@ -57,3 +122,37 @@ pub async fn register_plugin(event: RegisterEvent, stream: WebSocketStream<TcpSt
 		}
 	};
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::store::Settings;
+
+	#[test]
+	fn accepts_registration_without_token_by_default() {
+		assert!(accepts_registration(Some("secret"), None, &Settings::default(), false));
+	}
+
+	#[test]
+	fn rejects_registration_without_token_when_required() {
+		let settings = Settings {
+			require_plugin_tokens: true,
+			..Default::default()
+		};
+		assert!(!accepts_registration(Some("secret"), None, &settings, false));
+		assert!(accepts_registration(Some("secret"), Some("secret"), &settings, false));
+	}
+
+	#[test]
+	fn rejects_registration_without_token_while_connected() {
+		assert!(!accepts_registration(Some("secret"), None, &Settings::default(), true));
+	}
+
+	#[test]
+	fn rejects_wrong_token_and_plugins_not_launched() {
+		assert!(!accepts_registration(Some("secret"), Some("guess"), &Settings::default(), false));
+		assert!(!accepts_registration(None, None, &Settings::default(), false));
+		assert!(!accepts_registration(None, Some("secret"), &Settings::default(), false));
+	}
+}
@@ -52,7 +52,8 @@ pub async fn update_image(context: crate::shared::Context, image: Option<String>
 	if let Some(plugin) = DEVICE_NAMESPACES.read().await.get(&context.device[..2]) {
 		let image = match (context.controller.as_str(), image) {
 			("Encoder", Some(img)) => Some(to_encoder_jpeg_data_uri(&context, &img).await?),
-			(_, img) => img,
+			(_, Some(img)) => Some(crate::animation::first_frame(img)?),
+			(_, None) => None,
 		};
 
 		send_to_plugin(
@@ -68,6 +69,13 @@ pub async fn update_image(context: crate::shared::Context, image: Option<String>
 		.await?;
 	} else if context.device.starts_with("sd-") {
 		crate::elgato::update_image(&context, image.as_deref()).await?;
+	} else if context.device.starts_with("vd-") {
+		let image = match (context.controller.as_str(), image) {
+			("Encoder", Some(img)) => Some(to_encoder_jpeg_data_uri(&context, &img).await?),
+			(_, Some(img)) => Some(crate::animation::first_frame(img)?),
+			(_, None) => None,
+		};
+		crate::virtual_device::update_image(&context, image.as_deref()).await?;
 	}
 
 	Ok(())
@@ -101,6 +109,8 @@ pub async fn clear_screen(device: String) -> Result<(), anyhow::Error> {
 		.await?;
 	} else if device.starts_with("sd-") {
 		crate::elgato::clear_screen(&device).await?;
+	} else if device.starts_with("vd-") {
+		crate::virtual_device::clear_screen(&device).await?;
 	}
 
 	Ok(())
@@ -113,10 +123,12 @@ struct SetBrightnessEvent {
 	brightness: u8,
 }
 
-/// Set the brightness for all devices.
+/// Set the brightness for all devices that do not override it in their own settings.
 pub async fn set_brightness(brightness: u8) -> Result<(), anyhow::Error> {
-	for device in crate::shared::DEVICES.iter() {
-		set_device_brightness(&device.id, brightness).await?;
+	let devices = crate::shared::DEVICES.iter().map(|device| device.id.clone()).collect::<Vec<_>>();
+	for device in devices {
+		let settings = crate::store::profiles::get_device_settings(&device).await;
+		set_device_brightness(&device, settings.brightness.unwrap_or(brightness)).await?;
 	}
 
 	Ok(())
@@ -140,6 +152,8 @@ pub async fn set_device_brightness(device: &str, brightness: u8) -> Result<(), a
 		.await?;
 	} else if device.starts_with("sd-") {
 		crate::elgato::set_brightness(device, brightness).await;
+	} else if device.starts_with("vd-") {
+		crate::virtual_device::set_brightness(device, brightness).await;
 	}
 
 	Ok(())
@@ -0,0 +1,201 @@
+//! Recognition of short presses, long presses, double taps and auto-repeat on gesture actions.
+
+use super::keypad::{DEFAULT_HOLD, fire};
+
+use crate::shared::{ActionContext, ActionInstance};
+
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_LONG_PRESS_MS: u64 = 500;
+pub const DEFAULT_DOUBLE_TAP_MS: u64 = 250;
+pub const DEFAULT_REPEAT_INTERVAL_MS: u64 = 100;
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Gesture {
+	Press,
+	LongPress,
+	DoubleTap,
+	HoldRepeat,
+}
+
+/// The gestures assigned to children that have not been explicitly bound, in order.
+const DEFAULT_GESTURES: [Gesture; 4] = [Gesture::Press, Gesture::LongPress, Gesture::DoubleTap, Gesture::HoldRepeat];
+
+/// The children of a gesture action alongside the gestures they are bound to and the configured thresholds.
+struct Bindings {
+	children: Vec<(Gesture, ActionInstance)>,
+	long_press: Duration,
+	double_tap: Duration,
+	repeat_interval: Duration,
+}
+
+impl Bindings {
+	fn new(instance: &ActionInstance) -> Self {
+		let gestures = instance.settings.get("gestures").and_then(|v| v.as_array());
+		let children = instance
+			.children
+			.iter()
+			.flatten()
+			.enumerate()
+			.filter_map(|(i, child)| {
+				let gesture = match gestures.and_then(|v| v.get(i)) {
+					Some(value) => serde_json::from_value(value.clone()).ok()?,
+					None => *DEFAULT_GESTURES.get(i)?,
+				};
+				Some((gesture, child.clone()))
+			})
+			.collect();
+		let millis = |key: &str, default: u64| Duration::from_millis(instance.settings.get(key).and_then(|v| v.as_u64()).unwrap_or(default));
+
+		Self {
+			children,
+			long_press: millis("long_press_ms", DEFAULT_LONG_PRESS_MS),
+			double_tap: millis("double_tap_ms", DEFAULT_DOUBLE_TAP_MS),
+			repeat_interval: millis("repeat_interval_ms", DEFAULT_REPEAT_INTERVAL_MS).max(Duration::from_millis(10)),
+		}
+	}
+
+	fn child(&self, gesture: Gesture) -> Option<&ActionInstance> {
+		self.children.iter().find(|(g, _)| *g == gesture).map(|(_, child)| child)
+	}
+}
+
+/// Record a gesture for a child that has just been added to a gesture action, preferring one that is not yet bound.
+pub fn bind_new_child(parent: &mut ActionInstance) {
+	let count = parent.children.as_ref().map(|v| v.len()).unwrap_or_default();
+	if !parent.settings.is_object() {
+		parent.settings = serde_json::Value::Object(serde_json::Map::new());
+	}
+	let settings = parent.settings.as_object_mut().unwrap();
+	let mut gestures: Vec<Gesture> = settings.get("gestures").and_then(|v| serde_json::from_value(v.clone()).ok()).unwrap_or_default();
+
+	gestures.truncate(count.saturating_sub(1));
+	for i in gestures.len()..count.saturating_sub(1) {
+		gestures.push(DEFAULT_GESTURES.get(i).copied().unwrap_or(Gesture::Press));
+	}
+	let unbound = DEFAULT_GESTURES.into_iter().find(|gesture| !gestures.contains(gesture));
+	gestures.push(unbound.unwrap_or(Gesture::Press));
+
+	settings.insert("gestures".to_owned(), serde_json::to_value(gestures).unwrap());
+}
+
+#[derive(Default)]
+struct KeyState {
+	/// Incremented on every press, so that timers started by an earlier press can tell that they are stale.
+	generation: u64,
+	held: bool,
+	/// Whether the current press has already been recognised as a gesture, so that releasing it does nothing.
+	consumed: bool,
+	/// Whether a short tap is waiting to see if it will become a double tap.
+	pending_tap: bool,
+}
+
+static STATES: LazyLock<DashMap<ActionContext, KeyState>> = LazyLock::new(DashMap::new);
+
+fn is_current(context: &ActionContext, generation: u64) -> bool {
+	STATES.get(context).is_some_and(|state| state.generation == generation && state.held)
+}
+
+pub async fn key_down(instance: &ActionInstance) -> Result<(), anyhow::Error> {
+	let bindings = Bindings::new(instance);
+	let context = instance.context.clone();
+
+	let (generation, double_tapped) = {
+		let mut state = STATES.entry(context.clone()).or_default();
+		state.generation += 1;
+		state.held = true;
+		let double_tapped = state.pending_tap && bindings.child(Gesture::DoubleTap).is_some();
+		state.consumed = double_tapped;
+		state.pending_tap = false;
+		(state.generation, double_tapped)
+	};
+
+	if double_tapped {
+		return fire(bindings.child(Gesture::DoubleTap).unwrap(), DEFAULT_HOLD).await;
+	}
+
+	let long_press = bindings.child(Gesture::LongPress).cloned();
+	let hold_repeat = bindings.child(Gesture::HoldRepeat).cloned();
+	if long_press.is_none() && hold_repeat.is_none() {
+		return Ok(());
+	}
+
+	tokio::spawn(async move {
+		tokio::time::sleep(bindings.long_press).await;
+		match STATES.get_mut(&context) {
+			Some(mut state) if state.generation == generation && state.held => state.consumed = true,
+			_ => return,
+		}
+
+		if let Some(child) = long_press
+			&& let Err(error) = fire(&child, DEFAULT_HOLD).await
+		{
+			log::warn!("Failed to send long press to {}: {error:#}", child.context);
+		}
+
+		let Some(child) = hold_repeat else { return };
+		while is_current(&context, generation) {
+			if let Err(error) = fire(&child, DEFAULT_HOLD).await {
+				log::warn!("Failed to send repeated press to {}: {error:#}", child.context);
+				break;
+			}
+			tokio::time::sleep(bindings.repeat_interval).await;
+		}
+	});
+
+	Ok(())
+}
+
+pub async fn key_up(instance: &ActionInstance) -> Result<(), anyhow::Error> {
+	let bindings = Bindings::new(instance);
+	let context = instance.context.clone();
+
+	let generation = {
+		let mut state = STATES.entry(context.clone()).or_default();
+		state.held = false;
+		if state.consumed {
+			return Ok(());
+		}
+		state.generation
+	};
+
+	let press = bindings.child(Gesture::Press).cloned();
+	if bindings.child(Gesture::DoubleTap).is_none() {
+		return match press {
+			Some(press) => fire(&press, DEFAULT_HOLD).await,
+			None => Ok(()),
+		};
+	}
+
+	// Wait to see whether a second tap arrives before treating this as a short press.
+	if let Some(mut state) = STATES.get_mut(&context) {
+		state.pending_tap = true;
+	}
+	tokio::spawn(async move {
+		tokio::time::sleep(bindings.double_tap).await;
+		match STATES.get_mut(&context) {
+			Some(mut state) if state.generation == generation && state.pending_tap => state.pending_tap = false,
+			_ => return,
+		}
+		if let Some(press) = press
+			&& let Err(error) = fire(&press, DEFAULT_HOLD).await
+		{
+			log::warn!("Failed to send press to {}: {error:#}", press.context);
+		}
+	});
+
+	Ok(())
+}
+
+/// Stop tracking a held key whose release will not be delivered, for example because the profile was switched.
+pub fn release(context: &ActionContext) {
+	if let Some(mut state) = STATES.get_mut(context) {
+		state.held = false;
+		state.pending_tap = false;
+	}
+}
@@ -1,8 +1,9 @@
 use super::{GenericInstancePayload, send_to_plugin};
 
 use crate::events::frontend::instances::{key_moved, update_state};
-use crate::shared::{ActionContext, Context};
-use crate::store::profiles::{acquire_locks_mut, get_slot_mut, mark_profile_stale};
+use crate::events::inbound::misc::SwitchProfileEvent;
+use crate::shared::{ActionContext, ActionInstance, Context, page_parent};
+use crate::store::profiles::{acquire_locks_mut, get_instance_mut, get_slot_mut, mark_profile_stale};
 
 use std::sync::LazyLock;
 use std::time::Duration;
@@ -21,6 +22,50 @@ struct KeyEvent {
 	payload: GenericInstancePayload,
 }
 
+/// How long the children of parent actions are held for when no other duration is configured.
+pub(super) const DEFAULT_HOLD: Duration = Duration::from_millis(100);
+
+/// Send a single `keyDown` or `keyUp` event to an instance.
+pub(super) async fn send_key_event(instance: &ActionInstance, event: &'static str) -> Result<(), anyhow::Error> {
+	send_to_plugin(
+		&instance.action.plugin,
+		&KeyEvent {
+			event,
+			action: instance.action.uuid.clone(),
+			context: instance.context.clone(),
+			device: instance.context.device.clone(),
+			payload: GenericInstancePayload::new(instance),
+		},
+	)
+	.await
+}
+
+/// Send a complete press and release to an instance, as is done for the children of multi-actions.
+pub(super) async fn tap(instance: &ActionInstance, hold: Duration) -> Result<(), anyhow::Error> {
+	send_key_event(instance, "keyDown").await?;
+	tokio::time::sleep(hold).await;
+	send_key_event(instance, "keyUp").await
+}
+
+/// Advance the state of the child of a parent action after it has been released, as if it had been pressed directly.
+pub(super) async fn advance_state(child: &ActionInstance) -> Result<(), anyhow::Error> {
+	if child.states.len() == 2 && !child.action.disable_automatic_states {
+		let mut locks = acquire_locks_mut().await;
+		if let Some(instance) = get_instance_mut(&child.context, &mut locks).await? {
+			instance.current_state = (instance.current_state + 1) % (instance.states.len() as u16);
+			let _ = update_state(crate::APP_HANDLE.get().unwrap(), child.context.clone(), &mut locks).await;
+			mark_profile_stale(&child.context.device, &mut locks).await?;
+		}
+	}
+	Ok(())
+}
+
+/// Send a press held for a duration to the child of a parent action and advance its state.
+pub(super) async fn fire(child: &ActionInstance, hold: Duration) -> Result<(), anyhow::Error> {
+	tap(child, hold).await?;
+	advance_state(child).await
+}
+
 pub async fn key_down(device: &str, key: u8) -> Result<(), anyhow::Error> {
 	let mut locks = acquire_locks_mut().await;
 	let selected_profile = locks.device_stores.get_selected_profile(device)?;
@@ -35,67 +80,29 @@ pub async fn key_down(device: &str, key: u8) -> Result<(), anyhow::Error> {
 	KEY_DOWN_TARGETS.insert((device.to_owned(), key), context.clone());
 
 	let Some(instance) = get_slot_mut(&context, &mut locks).await? else { return Ok(()) };
-	if instance.action.uuid == "opendeck.multiaction" {
-		let children = instance.children.clone().unwrap_or_default();
-		let delays: Vec<u64> = instance
-			.settings
-			.get("delays")
-			.and_then(|v| v.as_array())
-			.map(|arr| arr.iter().filter_map(|v| v.as_u64()).collect())
-			.unwrap_or_default();
-
+	if matches!(instance.action.uuid.as_str(), "opendeck.folder" | "opendeck.folderback") {
+		let page = match &instance.page {
+			Some(page) => Some(page.id.clone()),
+			None => page_parent(&context.profile).map(|v| v.to_owned()),
+		};
 		drop(locks);
 
-		for (i, child) in children.iter().enumerate() {
-			send_to_plugin(
-				&child.action.plugin,
-				&KeyEvent {
-					event: "keyDown",
-					action: child.action.uuid.clone(),
-					context: child.context.clone(),
-					device: child.context.device.clone(),
-					payload: GenericInstancePayload::new(child),
-				},
-			)
+		// Pages are switched through the frontend in the same way as profiles, so that it stays in sync with the device.
+		if let Some(page) = page {
+			crate::events::inbound::misc::switch_profile(SwitchProfileEvent {
+				device: device.to_owned(),
+				profile: page,
+			})
 			.await?;
-
-			tokio::time::sleep(Duration::from_millis(100)).await;
-
-			send_to_plugin(
-				&child.action.plugin,
-				&KeyEvent {
-					event: "keyUp",
-					action: child.action.uuid.clone(),
-					context: child.context.clone(),
-					device: child.context.device.clone(),
-					payload: GenericInstancePayload::new(child),
-				},
-			)
-			.await?;
-
-			let delay = delays.get(i).copied().unwrap_or(100);
-			if delay > 0 {
-				tokio::time::sleep(Duration::from_millis(delay)).await;
-			}
-		}
-
-		let mut locks = acquire_locks_mut().await;
-
-		if let Some(instance) = get_slot_mut(&context, &mut locks).await?
-			&& let Some(children) = &mut instance.children
-		{
-			for child in &mut *children {
-				if child.states.len() == 2 && !child.action.disable_automatic_states {
-					child.current_state = (child.current_state + 1) % (child.states.len() as u16);
-				}
-			}
-
-			for child in children.iter().map(|x| x.context.clone()).collect::<Vec<_>>() {
-				let _ = update_state(crate::APP_HANDLE.get().unwrap(), child, &mut locks).await;
-			}
 		}
-
-		mark_profile_stale(device, &mut locks).await?;
+	} else if instance.action.uuid == "opendeck.gestureaction" {
+		let instance = instance.clone();
+		drop(locks);
+		super::gestures::key_down(&instance).await?;
+	} else if instance.action.uuid == "opendeck.multiaction" {
+		let instance = instance.clone();
+		drop(locks);
+		super::multi_action::key_down(instance).await?;
 	} else if instance.action.uuid == "opendeck.toggleaction" {
 		let children = instance.children.as_ref().unwrap();
 		if children.is_empty() {
@@ -145,13 +152,25 @@ pub async fn key_up(device: &str, key: u8) -> Result<(), anyhow::Error> {
 		return Ok(());
 	};
 	if context != expected_context {
+		drop(locks);
+		let expected_context = ActionContext::from_context(expected_context, 0);
+		super::gestures::release(&expected_context);
+		super::multi_action::release(&expected_context).await;
 		return Ok(());
 	}
 
 	let slot = get_slot_mut(&context, &mut locks).await?;
 	let Some(instance) = slot else { return Ok(()) };
 
-	if instance.action.uuid == "opendeck.toggleaction" {
+	if instance.action.uuid == "opendeck.gestureaction" {
+		let instance = instance.clone();
+		drop(locks);
+		return super::gestures::key_up(&instance).await;
+	} else if instance.action.uuid == "opendeck.multiaction" {
+		let instance = instance.clone();
+		drop(locks);
+		return super::multi_action::key_up(instance).await;
+	} else if instance.action.uuid == "opendeck.toggleaction" {
 		let index = instance.current_state as usize;
 		let children = instance.children.as_ref().unwrap();
 		if children.is_empty() {
@@ -170,7 +189,7 @@ pub async fn key_up(device: &str, key: u8) -> Result<(), anyhow::Error> {
 		)
 		.await?;
 		instance.current_state = ((index + 1) % instance.children.as_ref().unwrap().len()) as u16;
-	} else if instance.action.uuid != "opendeck.multiaction" {
+	} else if !matches!(instance.action.uuid.as_str(), "opendeck.folder" | "opendeck.folderback") {
 		if instance.states.len() == 2 && !instance.action.disable_automatic_states {
 			instance.current_state = (instance.current_state + 1) % (instance.states.len() as u16);
 		}
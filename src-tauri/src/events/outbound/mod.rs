@@ -2,8 +2,10 @@ pub mod applications;
 pub mod deep_link;
 pub mod devices;
 pub mod encoder;
+pub mod gestures;
 pub mod keypad;
 pub mod misc;
+pub mod multi_action;
 pub mod property_inspector;
 pub mod settings;
 pub mod states;
@@ -61,12 +63,8 @@ async fn send_to_plugin(plugin: &str, data: &impl Serialize) -> Result<(), anyho
 	if let Some(socket) = sockets.get_mut(plugin) {
 		socket.send(message).await?;
 	} else {
-		let mut queues = super::PLUGIN_QUEUES.write().await;
-		if queues.contains_key(plugin) {
-			queues.get_mut(plugin).unwrap().push(message);
-		} else {
-			queues.insert(plugin.to_owned(), vec![message]);
-		}
+		let value = serde_json::to_value(data)?;
+		super::enqueue_plugin_message(plugin, message, &value).await;
 	}
 
 	Ok(())
@@ -87,7 +85,6 @@ async fn send_to_all_plugins(data: &impl Serialize) -> Result<(), anyhow::Error>
 	Ok(())
 }
 
-#[allow(clippy::map_entry)]
 async fn send_to_property_inspector(context: &crate::shared::ActionContext, data: &impl Serialize) -> Result<(), anyhow::Error> {
 	let message = tokio_tungstenite::tungstenite::Message::Text(serde_json::to_string(data)?.into());
 	let mut sockets = super::PROPERTY_INSPECTOR_SOCKETS.lock().await;
@@ -95,12 +92,8 @@ async fn send_to_property_inspector(context: &crate::shared::ActionContext, data
 	if let Some(socket) = sockets.get_mut(&context.to_string()) {
 		socket.send(message).await?;
 	} else {
-		let mut queues = super::PROPERTY_INSPECTOR_QUEUES.write().await;
-		if queues.contains_key(&context.to_string()) {
-			queues.get_mut(&context.to_string()).unwrap().push(message);
-		} else {
-			queues.insert(context.to_string(), vec![message]);
-		}
+		let value = serde_json::to_value(data)?;
+		super::PROPERTY_INSPECTOR_QUEUES.write().await.entry(context.to_string()).or_default().push(message, &value);
 	}
 
 	Ok(())
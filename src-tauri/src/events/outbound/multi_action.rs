@@ -0,0 +1,279 @@
+//! Running the children of multi-actions in sequence on press or release, with support for repeated, looping, conditional and parallel steps,
+//! or holding them down for as long as the physical key is held.
+
+use super::keypad::{DEFAULT_HOLD, advance_state, fire, send_key_event};
+
+use crate::shared::{ActionContext, ActionInstance};
+use crate::store::profiles::{acquire_locks, get_instance};
+
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+const DEFAULT_DELAY_MS: u64 = 100;
+/// The shortest time between the presses of a step that repeats until cancelled, so that it cannot flood the plugin.
+const MIN_LOOP_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Only run a step when another child of the multi-action is in a given state.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Condition {
+	pub child: usize,
+	pub state: u16,
+}
+
+/// How a child of a multi-action is run, stored in the `steps` array of the parent's settings.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Step {
+	/// The number of times to press the child, where 0 repeats it until the multi-action is pressed again.
+	pub repeat: u32,
+	/// Run the child at the same time as the previous child rather than after it.
+	pub parallel: bool,
+	pub condition: Option<Condition>,
+	/// How long the child is held for each time it is pressed.
+	pub hold_ms: u64,
+}
+
+impl Default for Step {
+	fn default() -> Self {
+		Self {
+			repeat: 1,
+			parallel: false,
+			condition: None,
+			hold_ms: DEFAULT_HOLD.as_millis() as u64,
+		}
+	}
+}
+
+/// When a multi-action runs its children, stored as `trigger` in the parent's settings.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+	/// Run the sequence when the key is pressed.
+	#[default]
+	Press,
+	/// Run the sequence when the key is released.
+	Release,
+	/// Press every child when the key is pressed and release them when the key is released.
+	Mirror,
+}
+
+impl Trigger {
+	fn of(instance: &ActionInstance) -> Self {
+		instance.settings.get("trigger").and_then(|v| serde_json::from_value(v.clone()).ok()).unwrap_or_default()
+	}
+}
+
+/// The running sequences of multi-actions, keyed by their context, alongside an identifier for each run and a way to cancel it.
+static RUNNING: LazyLock<DashMap<ActionContext, (u64, watch::Sender<bool>)>> = LazyLock::new(DashMap::new);
+static NEXT_RUN: AtomicU64 = AtomicU64::new(0);
+
+/// The children of multi-actions in mirror mode that are currently held down, keyed by the context of the multi-action.
+static HELD: LazyLock<DashMap<ActionContext, Vec<ActionInstance>>> = LazyLock::new(DashMap::new);
+
+pub async fn key_down(instance: ActionInstance) -> Result<(), anyhow::Error> {
+	match Trigger::of(&instance) {
+		Trigger::Press => start_or_cancel(instance),
+		Trigger::Release => {}
+		Trigger::Mirror => press_children(instance).await?,
+	}
+	Ok(())
+}
+
+pub async fn key_up(instance: ActionInstance) -> Result<(), anyhow::Error> {
+	match Trigger::of(&instance) {
+		Trigger::Press => {}
+		Trigger::Release => start_or_cancel(instance),
+		Trigger::Mirror => release_children(&instance.context).await?,
+	}
+	Ok(())
+}
+
+/// Release the children of a multi-action whose key release will not be delivered, for example because the profile was switched.
+pub async fn release(context: &ActionContext) {
+	if let Err(error) = release_children(context).await {
+		log::warn!("Failed to release children of multi-action {context}: {error:#}");
+	}
+}
+
+/// Start running a multi-action, or cancel it if it is already running.
+fn start_or_cancel(instance: ActionInstance) {
+	if let Some((_, (_, cancel))) = RUNNING.remove(&instance.context) {
+		let _ = cancel.send(true);
+		return;
+	}
+
+	let id = NEXT_RUN.fetch_add(1, Ordering::Relaxed);
+	let (cancel, cancelled) = watch::channel(false);
+	RUNNING.insert(instance.context.clone(), (id, cancel));
+
+	tokio::spawn(async move {
+		if let Err(error) = run(&instance, cancelled).await {
+			log::warn!("Failed to run multi-action {}: {error:#}", instance.context);
+		}
+		RUNNING.remove_if(&instance.context, |_, (running, _)| *running == id);
+	});
+}
+
+/// Send `keyDown` to each child whose condition is met, keeping track of them so that they can be released later.
+async fn press_children(instance: ActionInstance) -> Result<(), anyhow::Error> {
+	release_children(&instance.context).await?;
+
+	let children = instance.children.clone().unwrap_or_default();
+	let steps = instance.settings.get("steps").and_then(|v| v.as_array());
+	let mut pressed = vec![];
+	for (index, child) in children.iter().enumerate() {
+		let step = steps.and_then(|v| v.get(index)).and_then(|v| serde_json::from_value::<Step>(v.clone()).ok()).unwrap_or_default();
+		if condition_met(&children, step.condition).await {
+			pressed.push(child.clone());
+		}
+	}
+	HELD.insert(instance.context.clone(), pressed.clone());
+
+	for child in &pressed {
+		send_key_event(child, "keyDown").await?;
+	}
+	Ok(())
+}
+
+/// Send `keyUp` to the held children of a multi-action in the reverse order to which they were pressed.
+async fn release_children(context: &ActionContext) -> Result<(), anyhow::Error> {
+	let Some((_, pressed)) = HELD.remove(context) else { return Ok(()) };
+	for child in pressed.iter().rev() {
+		send_key_event(child, "keyUp").await?;
+		advance_state(child).await?;
+	}
+	Ok(())
+}
+
+/// Cancel a running multi-action and release any of its children that are held, as is done when it is removed.
+pub async fn cancel(context: &ActionContext) {
+	if let Some((_, (_, cancel))) = RUNNING.remove(context) {
+		let _ = cancel.send(true);
+	}
+	release_without_advancing(context).await;
+}
+
+/// Send `keyUp` to the held children of a multi-action without advancing their states, as they are being removed or their device is no longer available.
+async fn release_without_advancing(context: &ActionContext) {
+	let Some((_, pressed)) = HELD.remove(context) else { return };
+	for child in pressed.iter().rev() {
+		if let Err(error) = send_key_event(child, "keyUp").await {
+			log::warn!("Failed to release child {} of multi-action {context}: {error:#}", child.context);
+		}
+	}
+}
+
+/// Release the held children of the multi-actions of a device that has been disconnected, whose key releases will never be delivered.
+pub async fn release_device(device: &str) {
+	let held = HELD.iter().map(|entry| entry.key().clone()).filter(|context| context.device == device).collect::<Vec<_>>();
+	for context in held {
+		release_without_advancing(&context).await;
+	}
+}
+
+/// Cancel the running multi-actions of a device, as is done when its page changes or it is disconnected and so they can no longer be pressed again to stop them.
+pub fn cancel_device(device: &str) {
+	RUNNING.retain(|context, (_, cancel)| {
+		if context.device != device {
+			return true;
+		}
+		let _ = cancel.send(true);
+		false
+	});
+}
+
+/// Sleep for a duration, returning `false` if the run was cancelled first.
+async fn wait(duration: Duration, cancelled: &mut watch::Receiver<bool>) -> bool {
+	if *cancelled.borrow() {
+		return false;
+	}
+	tokio::select! {
+		_ = tokio::time::sleep(duration) => true,
+		_ = cancelled.wait_for(|v| *v) => false,
+	}
+}
+
+async fn condition_met(children: &[ActionInstance], condition: Option<Condition>) -> bool {
+	let Some(condition) = condition else { return true };
+	let Some(child) = children.get(condition.child) else { return false };
+	let locks = acquire_locks().await;
+	matches!(get_instance(&child.context, &locks).await, Ok(Some(instance)) if instance.current_state == condition.state)
+}
+
+async fn run_step(child: &ActionInstance, step: Step, delay: Duration, mut cancelled: watch::Receiver<bool>) -> Result<(), anyhow::Error> {
+	let hold = Duration::from_millis(step.hold_ms);
+	let delay = if step.repeat == 0 { delay.max(MIN_LOOP_INTERVAL.saturating_sub(hold)) } else { delay };
+	let mut count = 0;
+	while step.repeat == 0 || count < step.repeat {
+		if *cancelled.borrow() {
+			break;
+		}
+		fire(child, hold).await?;
+		count += 1;
+		if !wait(delay, &mut cancelled).await {
+			break;
+		}
+	}
+	Ok(())
+}
+
+async fn run(instance: &ActionInstance, cancelled: watch::Receiver<bool>) -> Result<(), anyhow::Error> {
+	let children = instance.children.clone().unwrap_or_default();
+	let delays = instance.settings.get("delays").and_then(|v| v.as_array());
+	let steps = instance.settings.get("steps").and_then(|v| v.as_array());
+	let step = |index: usize| steps.and_then(|v| v.get(index)).and_then(|v| serde_json::from_value::<Step>(v.clone()).ok()).unwrap_or_default();
+	let delay = |index: usize| Duration::from_millis(delays.and_then(|v| v.get(index)).and_then(|v| v.as_u64()).unwrap_or(DEFAULT_DELAY_MS));
+
+	// Each group is a child followed by any children that run in parallel with it.
+	let mut groups: Vec<Vec<usize>> = vec![];
+	for index in 0..children.len() {
+		match groups.last_mut() {
+			Some(group) if step(index).parallel => group.push(index),
+			_ => groups.push(vec![index]),
+		}
+	}
+
+	for group in groups {
+		if *cancelled.borrow() {
+			break;
+		}
+
+		let mut runs = vec![];
+		for index in group {
+			let step = step(index);
+			if condition_met(&children, step.condition).await {
+				runs.push(run_step(&children[index], step, delay(index), cancelled.clone()));
+			}
+		}
+		for result in futures::future::join_all(runs).await {
+			result?;
+		}
+	}
+
+	Ok(())
+}
+
+/// Update the steps of a multi-action after one of its children has been removed, dropping conditions that referred to it.
+pub fn remove_child(settings: &mut serde_json::Map<String, serde_json::Value>, index: usize) {
+	let Some(steps) = settings.get_mut("steps").and_then(|v| v.as_array_mut()) else { return };
+	if index < steps.len() {
+		steps.remove(index);
+	}
+	for value in steps.iter_mut() {
+		let Ok(mut step) = serde_json::from_value::<Step>(value.clone()) else { continue };
+		step.condition = match step.condition {
+			Some(condition) if condition.child == index => None,
+			Some(condition) if condition.child > index => Some(Condition {
+				child: condition.child - 1,
+				..condition
+			}),
+			other => other,
+		};
+		*value = serde_json::to_value(step).unwrap();
+	}
+}
@@ -0,0 +1,79 @@
+//! Bounded queues of messages for plugins and property inspectors that are not currently connected.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use tokio_tungstenite::tungstenite::Message;
+
+const MAX_QUEUE_LENGTH: usize = 512;
+const MAX_QUEUE_AGE: Duration = Duration::from_secs(300);
+
+/// Events of which only the most recent copy for a given target is meaningful.
+const COALESCED_EVENTS: [&str; 5] = ["didReceiveSettings", "didReceiveGlobalSettings", "titleParametersDidChange", "setImage", "setBrightness"];
+
+struct QueuedMessage {
+	message: Message,
+	queued_at: Instant,
+	key: Option<String>,
+}
+
+#[derive(Default)]
+pub struct MessageQueue {
+	messages: VecDeque<QueuedMessage>,
+}
+
+/// Identify the target of a coalescable event, so that older copies of it can be discarded.
+fn coalesce_key(value: &serde_json::Value) -> Option<String> {
+	let event = value.get("event")?.as_str()?;
+	if !COALESCED_EVENTS.contains(&event) {
+		return None;
+	}
+
+	let mut key = event.to_owned();
+	for field in ["context", "device", "controller", "position"] {
+		key += &format!("|{}", value.get(field).unwrap_or(&serde_json::Value::Null));
+	}
+	Some(key)
+}
+
+impl MessageQueue {
+	pub fn push(&mut self, message: Message, value: &serde_json::Value) {
+		self.prune();
+
+		let key = coalesce_key(value);
+		if let Some(key) = &key {
+			self.messages.retain(|queued| queued.key.as_ref() != Some(key));
+		}
+		if self.messages.len() >= MAX_QUEUE_LENGTH {
+			self.messages.pop_front();
+		}
+
+		self.messages.push_back(QueuedMessage {
+			message,
+			queued_at: Instant::now(),
+			key,
+		});
+	}
+
+	/// Discard messages that have been queued for longer than the maximum age.
+	pub fn prune(&mut self) {
+		while self.messages.front().is_some_and(|queued| queued.queued_at.elapsed() > MAX_QUEUE_AGE) {
+			self.messages.pop_front();
+		}
+	}
+
+	/// Get the number of messages that have not expired, without discarding those that have.
+	pub fn len(&self) -> usize {
+		self.messages.len() - self.messages.partition_point(|queued| queued.queued_at.elapsed() > MAX_QUEUE_AGE)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Consume the queue, returning the messages that have not expired in the order they were queued.
+	pub fn into_messages(mut self) -> impl Iterator<Item = Message> {
+		self.prune();
+		self.messages.into_iter().map(|queued| queued.message)
+	}
+}
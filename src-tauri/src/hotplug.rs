@@ -0,0 +1,211 @@
+//! Detection of Elgato devices being plugged in or unplugged, so that they are connected without waiting for the next poll.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const ELGATO_VENDOR_ID: u16 = 0x0fd9;
+
+/// How long to wait after a device appears before connecting to it, giving udev rules time to set its permissions.
+const SETTLE_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HotplugAction {
+	Added,
+	Removed,
+}
+
+#[derive(Clone, Debug)]
+pub struct HotplugEvent {
+	pub action: HotplugAction,
+	pub vendor_id: Option<u16>,
+}
+
+/// A blocking source of hotplug events, such as the udev netlink socket.
+pub trait HotplugSource: Send + 'static {
+	/// Wait for the next event, returning `None` once the source has been closed.
+	fn next_event(&mut self) -> Option<HotplugEvent>;
+}
+
+/// The effects of hotplug events, which can be replaced to observe how events are handled.
+pub trait HotplugHandler: Send + Sync + 'static {
+	/// Connect to devices that have been plugged in.
+	fn connect(&self) -> impl Future<Output = ()> + Send;
+	/// Drop devices that have been unplugged.
+	fn disconnect(&self) -> impl Future<Output = ()> + Send;
+}
+
+pub struct ElgatoHandler;
+
+impl HotplugHandler for ElgatoHandler {
+	async fn connect(&self) {
+		crate::elgato::initialise_devices().await;
+	}
+
+	async fn disconnect(&self) {
+		crate::elgato::drop_disconnected_devices().await;
+	}
+}
+
+/// Parse the vendor ID from the `PRODUCT` property of a USB device, which has the form `vendor/product/revision` in hexadecimal.
+pub fn parse_product_vendor(product: &str) -> Option<u16> {
+	u16::from_str_radix(product.split('/').next()?, 16).ok()
+}
+
+pub async fn handle_event(event: HotplugEvent, handler: &impl HotplugHandler) {
+	if event.vendor_id != Some(ELGATO_VENDOR_ID) {
+		return;
+	}
+
+	match event.action {
+		HotplugAction::Added => {
+			tokio::time::sleep(SETTLE_DELAY).await;
+			handler.connect().await;
+		}
+		HotplugAction::Removed => handler.disconnect().await,
+	}
+}
+
+/// Handle events from a source on a dedicated thread until it is closed.
+pub fn listen(mut source: impl HotplugSource, handler: impl HotplugHandler) -> std::thread::JoinHandle<()> {
+	let handler = Arc::new(handler);
+	std::thread::spawn(move || {
+		while let Some(event) = source.next_event() {
+			let handler = handler.clone();
+			tauri::async_runtime::spawn(async move { handle_event(event, handler.as_ref()).await });
+		}
+	})
+}
+
+#[cfg(target_os = "linux")]
+mod udev_source {
+	use super::{HotplugAction, HotplugEvent, HotplugSource, parse_product_vendor};
+
+	use std::os::fd::AsRawFd;
+
+	pub struct UdevSource {
+		socket: udev::MonitorSocket,
+	}
+
+	impl UdevSource {
+		pub fn new() -> std::io::Result<Self> {
+			// Devices are connected once their hidraw node exists, but only the USB device itself still carries a vendor ID when it is removed.
+			let socket = udev::MonitorBuilder::new()?.match_subsystem("hidraw")?.match_subsystem_devtype("usb", "usb_device")?.listen()?;
+			Ok(Self { socket })
+		}
+	}
+
+	fn vendor_id(device: &udev::Device) -> Option<u16> {
+		let product = match device.property_value("PRODUCT") {
+			Some(product) => product.to_owned(),
+			None => device.parent_with_subsystem_devtype("usb", "usb_device").ok()??.property_value("PRODUCT")?.to_owned(),
+		};
+		parse_product_vendor(&product.to_string_lossy())
+	}
+
+	impl HotplugSource for UdevSource {
+		fn next_event(&mut self) -> Option<HotplugEvent> {
+			loop {
+				if let Some(event) = self.socket.iter().next() {
+					let action = match (event.event_type(), event.subsystem().and_then(|v| v.to_str())) {
+						(udev::EventType::Add, Some("hidraw")) => HotplugAction::Added,
+						(udev::EventType::Remove, Some("usb")) => HotplugAction::Removed,
+						_ => continue,
+					};
+					return Some(HotplugEvent { action, vendor_id: vendor_id(&event) });
+				}
+
+				let mut fd = libc::pollfd {
+					fd: self.socket.as_raw_fd(),
+					events: libc::POLLIN,
+					revents: 0,
+				};
+				if unsafe { libc::poll(&mut fd, 1, -1) } < 0 && std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+					return None;
+				}
+			}
+		}
+	}
+}
+
+/// Start listening for hotplug events, returning whether a listener could be started on this platform.
+pub fn init_hotplug() -> bool {
+	#[cfg(target_os = "linux")]
+	match udev_source::UdevSource::new() {
+		Ok(source) => {
+			listen(source, ElgatoHandler);
+			return true;
+		}
+		Err(error) => log::warn!("Failed to listen for udev events, falling back to polling: {error}"),
+	}
+
+	false
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::sync::mpsc;
+
+	struct FakeSource(std::vec::IntoIter<HotplugEvent>);
+
+	impl HotplugSource for FakeSource {
+		fn next_event(&mut self) -> Option<HotplugEvent> {
+			self.0.next()
+		}
+	}
+
+	struct RecordingHandler(mpsc::Sender<HotplugAction>);
+
+	impl HotplugHandler for RecordingHandler {
+		async fn connect(&self) {
+			let _ = self.0.send(HotplugAction::Added);
+		}
+
+		async fn disconnect(&self) {
+			let _ = self.0.send(HotplugAction::Removed);
+		}
+	}
+
+	fn event(action: HotplugAction, vendor_id: Option<u16>) -> HotplugEvent {
+		HotplugEvent { action, vendor_id }
+	}
+
+	#[test]
+	fn parses_vendor_from_product() {
+		assert_eq!(parse_product_vendor("fd9/80/100"), Some(ELGATO_VENDOR_ID));
+		assert_eq!(parse_product_vendor("46d/c52b/1211"), Some(0x046d));
+		assert_eq!(parse_product_vendor("not/a/product"), None);
+	}
+
+	#[test]
+	fn listen_handles_only_elgato_events() {
+		let source = FakeSource(
+			vec![
+				event(HotplugAction::Added, Some(ELGATO_VENDOR_ID)),
+				event(HotplugAction::Added, Some(0x046d)),
+				event(HotplugAction::Removed, None),
+				event(HotplugAction::Removed, Some(ELGATO_VENDOR_ID)),
+			]
+			.into_iter(),
+		);
+		let (sender, receiver) = mpsc::channel();
+		listen(source, RecordingHandler(sender)).join().unwrap();
+
+		let timeout = SETTLE_DELAY * 4;
+		let handled = [receiver.recv_timeout(timeout).unwrap(), receiver.recv_timeout(timeout).unwrap()];
+		// Devices that are removed are dropped straight away, whereas those that are added are only connected after settling.
+		assert_eq!(handled, [HotplugAction::Removed, HotplugAction::Added]);
+		assert!(receiver.recv_timeout(SETTLE_DELAY).is_err());
+	}
+
+	#[tokio::test]
+	async fn added_devices_are_connected_after_settling() {
+		let (sender, receiver) = mpsc::channel();
+		let handler = RecordingHandler(sender);
+		let start = std::time::Instant::now();
+		handle_event(event(HotplugAction::Added, Some(ELGATO_VENDOR_ID)), &handler).await;
+		assert!(start.elapsed() >= SETTLE_DELAY);
+		assert_eq!(receiver.try_recv(), Ok(HotplugAction::Added));
+	}
+}
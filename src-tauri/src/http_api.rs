@@ -0,0 +1,268 @@
+//! An opt-in REST API on localhost for controlling devices and profiles from scripts and dashboards.
+
+use crate::events::inbound::misc::SwitchProfileEvent;
+use crate::events::inbound::{ContextAndPayloadEvent, states};
+use crate::shared::{ActionContext, Context, DEVICES};
+use crate::store::Settings;
+use crate::store::profiles::{acquire_locks_mut, get_device_profiles, get_slot_mut};
+
+use std::io::Read;
+use std::sync::{Arc, LazyLock, Mutex};
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+const MAX_BODY_LENGTH: u64 = 16 * 1024 * 1024;
+
+/// The running server and the port it is bound to.
+static SERVER: LazyLock<Mutex<Option<(u16, Arc<Server>)>>> = LazyLock::new(|| Mutex::new(None));
+
+struct ApiError {
+	status: u16,
+	message: String,
+}
+
+impl ApiError {
+	fn new(status: u16, message: impl Into<String>) -> Self {
+		Self { status, message: message.into() }
+	}
+}
+
+impl From<anyhow::Error> for ApiError {
+	fn from(error: anyhow::Error) -> Self {
+		Self::new(500, format!("{error:#}"))
+	}
+}
+
+impl From<serde_json::Error> for ApiError {
+	fn from(error: serde_json::Error) -> Self {
+		Self::new(400, format!("invalid request body: {error}"))
+	}
+}
+
+type ApiResult = Result<Value, ApiError>;
+
+/// Generate a new bearer token for the API.
+pub fn generate_token() -> String {
+	use rand::{Rng, distr::Alphanumeric};
+	rand::rng().sample_iter(&Alphanumeric).take(40).map(char::from).collect()
+}
+
+/// Start, stop or restart the server to match the current settings.
+pub fn update_api(settings: &Settings) {
+	let mut server = SERVER.lock().unwrap();
+	let wanted = settings.api_enabled.then_some(settings.api_port);
+	if server.as_ref().map(|(port, _)| *port) == wanted {
+		return;
+	}
+
+	if let Some((_, existing)) = server.take() {
+		existing.unblock();
+	}
+	let Some(port) = wanted else { return };
+
+	match Server::http(("127.0.0.1", port)) {
+		Ok(new) => {
+			let new = Arc::new(new);
+			*server = Some((port, new.clone()));
+			std::thread::spawn(move || serve(new));
+			log::info!("Serving the REST API on port {port}");
+		}
+		Err(error) => log::error!("Failed to start the REST API on port {port}: {error}"),
+	}
+}
+
+fn serve(server: Arc<Server>) {
+	for mut request in server.incoming_requests() {
+		// Reject unauthorised requests before reading a body of up to the maximum length from them.
+		let result = authorise(&request, &crate::store::get_settings().value)
+			.and_then(|_| read_body(&mut request))
+			.and_then(|body| tauri::async_runtime::block_on(handle(&request, body)));
+		let (status, value) = match result {
+			Ok(value) => (200, value),
+			Err(error) => (error.status, json!({ "error": error.message })),
+		};
+
+		let mut response = Response::from_string(value.to_string()).with_status_code(status);
+		response.add_header(Header {
+			field: "Content-Type".parse().unwrap(),
+			value: "application/json".parse().unwrap(),
+		});
+		let _ = request.respond(response);
+	}
+}
+
+fn read_body(request: &mut Request) -> Result<Option<Value>, ApiError> {
+	let mut body = String::new();
+	request
+		.as_reader()
+		.take(MAX_BODY_LENGTH)
+		.read_to_string(&mut body)
+		.map_err(|error| ApiError::new(400, format!("failed to read request body: {error}")))?;
+	if body.trim().is_empty() { Ok(None) } else { Ok(Some(serde_json::from_str(&body)?)) }
+}
+
+/// Compare two strings in time that does not depend on where they first differ.
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+	a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn authorise(request: &Request, settings: &Settings) -> Result<(), ApiError> {
+	let provided = request
+		.headers()
+		.iter()
+		.find(|header| header.field.equiv("Authorization"))
+		.and_then(|header| header.value.as_str().strip_prefix("Bearer "));
+	match provided {
+		Some(token) if !settings.api_token.is_empty() && constant_time_eq(token.trim(), &settings.api_token) => Ok(()),
+		_ => Err(ApiError::new(401, "missing or invalid bearer token")),
+	}
+}
+
+fn parse_body<T: for<'a> Deserialize<'a>>(body: Option<Value>) -> Result<T, ApiError> {
+	Ok(serde_json::from_value(body.unwrap_or(Value::Null))?)
+}
+
+fn require_device(device: &str) -> Result<(), ApiError> {
+	if DEVICES.contains_key(device) {
+		Ok(())
+	} else {
+		Err(ApiError::new(404, format!("device {device} not found")))
+	}
+}
+
+fn parse_position(position: &str) -> Result<u8, ApiError> {
+	position.parse().map_err(|_| ApiError::new(400, format!("invalid position {position}")))
+}
+
+async fn handle(request: &Request, body: Option<Value>) -> ApiResult {
+	let path = request.url().split('?').next().unwrap_or_default();
+	let segments = path
+		.trim_matches('/')
+		.split('/')
+		.map(|segment| urlencoding::decode(segment).map(|v| v.into_owned()))
+		.collect::<Result<Vec<_>, _>>()
+		.map_err(|_| ApiError::new(400, "invalid path encoding"))?;
+	let segments = segments.iter().map(|v| v.as_str()).collect::<Vec<_>>();
+
+	match (request.method(), segments.as_slice()) {
+		(Method::Get, ["v1", "settings"]) => {
+			let mut value = serde_json::to_value(crate::store::get_settings().value)?;
+			if let Some(map) = value.as_object_mut() {
+				map.remove("api_token");
+				map.remove("mqtt_password");
+			}
+			Ok(value)
+		}
+		(Method::Get, ["v1", "stats", "image-cache"]) => Ok(json!(crate::image_cache::stats())),
+		(Method::Get, ["v1", "devices"]) => Ok(json!(DEVICES.iter().map(|device| device.value().clone()).collect::<Vec<_>>())),
+		(Method::Get, ["v1", "devices", device, "profiles"]) => list_profiles(device).await,
+		(Method::Post, ["v1", "devices", device, "profile"]) => switch_profile(device, parse_body(body)?).await,
+		(Method::Post, ["v1", "devices", device, controller @ ("keys" | "encoders"), position, "press"]) => {
+			press(device, if *controller == "keys" { "Keypad" } else { "Encoder" }, parse_position(position)?).await
+		}
+		(Method::Post, ["v1", "devices", device, "keys", position, "title"]) => {
+			let context = key_context(device, parse_position(position)?).await?;
+			states::set_title(ContextAndPayloadEvent { context, payload: parse_body(body)? }).await?;
+			Ok(json!({}))
+		}
+		(Method::Post, ["v1", "devices", device, "keys", position, "image"]) => {
+			let context = key_context(device, parse_position(position)?).await?;
+			states::set_image(ContextAndPayloadEvent { context, payload: parse_body(body)? }).await?;
+			Ok(json!({}))
+		}
+		(Method::Get, ["v1", "variables"]) => Ok(json!(crate::variables::VARIABLES.read().await.value)),
+		(Method::Put, ["v1", "variables", name]) => {
+			let body: SetVariableBody = parse_body(body)?;
+			set_variable(name, Some(body.value), body.device, body.profile).await
+		}
+		(Method::Delete, ["v1", "variables", name]) => {
+			let body: VariableScope = parse_body(body.or_else(|| Some(json!({}))))?;
+			set_variable(name, None, body.device, body.profile).await
+		}
+		(_, ["v1", ..]) => Err(ApiError::new(404, format!("no endpoint for {} {path}", request.method()))),
+		_ => Err(ApiError::new(404, "unknown API version")),
+	}
+}
+
+async fn list_profiles(device: &str) -> ApiResult {
+	require_device(device)?;
+	let selected = acquire_locks_mut().await.device_stores.get_selected_profile(device)?;
+	Ok(json!({ "profiles": get_device_profiles(device)?, "selected": selected }))
+}
+
+#[derive(Deserialize)]
+struct SwitchProfileBody {
+	profile: String,
+}
+
+async fn switch_profile(device: &str, body: SwitchProfileBody) -> ApiResult {
+	require_device(device)?;
+	if !get_device_profiles(device)?.contains(&body.profile) {
+		return Err(ApiError::new(404, format!("profile {} not found", body.profile)));
+	}
+
+	// Profiles are switched through the frontend so that it stays in sync with the device.
+	crate::events::inbound::misc::switch_profile(SwitchProfileEvent {
+		device: device.to_owned(),
+		profile: body.profile,
+	})
+	.await?;
+	Ok(json!({}))
+}
+
+#[derive(Deserialize)]
+struct VariableScope {
+	device: Option<String>,
+	profile: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SetVariableBody {
+	value: String,
+	device: Option<String>,
+	profile: Option<String>,
+}
+
+async fn set_variable(name: &str, value: Option<String>, device: Option<String>, profile: Option<String>) -> ApiResult {
+	if let Some(device) = &device {
+		require_device(device)?;
+	}
+	crate::variables::set_variable(name, value, device.as_deref(), profile.as_deref())
+		.await
+		.map_err(|error| ApiError::new(400, format!("{error:#}")))?;
+	Ok(json!({}))
+}
+
+async fn press(device: &str, controller: &str, position: u8) -> ApiResult {
+	require_device(device)?;
+	let profile = acquire_locks_mut().await.device_stores.get_selected_profile(device)?;
+	crate::events::frontend::instances::trigger_virtual_press(Context {
+		device: device.to_owned(),
+		profile,
+		controller: controller.to_owned(),
+		position,
+	})
+	.await
+	.map_err(|error| ApiError::new(500, error.description))?;
+	Ok(json!({}))
+}
+
+/// Find the context of the instance occupying a key on the selected profile of a device.
+async fn key_context(device: &str, position: u8) -> Result<ActionContext, ApiError> {
+	require_device(device)?;
+	let mut locks = acquire_locks_mut().await;
+	let profile = locks.device_stores.get_selected_profile(device)?;
+	let context = Context {
+		device: device.to_owned(),
+		profile,
+		controller: "Keypad".to_owned(),
+		position,
+	};
+
+	match get_slot_mut(&context, &mut locks).await? {
+		Some(instance) => Ok(instance.context.clone()),
+		None => Err(ApiError::new(404, format!("no action on key {position}"))),
+	}
+}
@@ -0,0 +1,98 @@
+//! Caching of images converted to device formats, and tracking of what each key last displayed so that identical frames are not written again.
+
+use std::collections::{HashMap, VecDeque};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+
+use dashmap::DashMap;
+use serde::Serialize;
+
+/// The maximum number of converted images kept in memory.
+const MAX_ENTRIES: usize = 256;
+
+type CacheKey = (u64, String);
+
+#[derive(Default)]
+struct Cache {
+	entries: HashMap<CacheKey, Arc<[u8]>>,
+	order: VecDeque<CacheKey>,
+}
+
+static CACHE: LazyLock<Mutex<Cache>> = LazyLock::new(|| Mutex::new(Cache::default()));
+
+/// The hash of the frame last written to each slot, keyed by device, controller and position.
+static LAST_WRITTEN: LazyLock<DashMap<(String, String, u8), u64>> = LazyLock::new(DashMap::new);
+
+static HITS: AtomicU64 = AtomicU64::new(0);
+static MISSES: AtomicU64 = AtomicU64::new(0);
+static SKIPPED_WRITES: AtomicU64 = AtomicU64::new(0);
+
+pub fn content_hash(bytes: &[u8]) -> u64 {
+	let mut hasher = DefaultHasher::new();
+	bytes.hash(&mut hasher);
+	hasher.finish()
+}
+
+/// Convert an image with `convert`, reusing the result of an earlier conversion of identical bytes.
+///
+/// `format` should uniquely identify the output of `convert`, such as the device kind and image rotation.
+pub fn convert(bytes: &[u8], format: &str, convert: impl FnOnce(&[u8]) -> Result<Vec<u8>, anyhow::Error>) -> Result<Arc<[u8]>, anyhow::Error> {
+	let key = (content_hash(bytes), format.to_owned());
+	if let Some(data) = CACHE.lock().unwrap().entries.get(&key) {
+		HITS.fetch_add(1, Ordering::Relaxed);
+		return Ok(data.clone());
+	}
+	MISSES.fetch_add(1, Ordering::Relaxed);
+
+	let data: Arc<[u8]> = convert(bytes)?.into();
+	let mut cache = CACHE.lock().unwrap();
+	if cache.entries.insert(key.clone(), data.clone()).is_none() {
+		cache.order.push_back(key);
+	}
+	while cache.order.len() > MAX_ENTRIES {
+		if let Some(oldest) = cache.order.pop_front() {
+			cache.entries.remove(&oldest);
+		}
+	}
+	Ok(data)
+}
+
+/// Check whether a frame differs from the one last written to a slot, recording it as written if so.
+pub fn should_write(device: &str, controller: &str, position: u8, data: &[u8]) -> bool {
+	let hash = content_hash(data);
+	let previous = LAST_WRITTEN.insert((device.to_owned(), controller.to_owned(), position), hash);
+	if previous == Some(hash) {
+		SKIPPED_WRITES.fetch_add(1, Ordering::Relaxed);
+		false
+	} else {
+		true
+	}
+}
+
+/// Forget what a slot displays, for example because it has been cleared.
+pub fn forget(device: &str, controller: &str, position: u8) {
+	LAST_WRITTEN.remove(&(device.to_owned(), controller.to_owned(), position));
+}
+
+/// Forget what every slot of a device displays, for example because its screen has been cleared or reset.
+pub fn forget_device(device: &str) {
+	LAST_WRITTEN.retain(|(id, _, _), _| id != device);
+}
+
+#[derive(Serialize)]
+pub struct CacheStats {
+	pub hits: u64,
+	pub misses: u64,
+	pub skipped_writes: u64,
+	pub entries: usize,
+}
+
+pub fn stats() -> CacheStats {
+	CacheStats {
+		hits: HITS.load(Ordering::Relaxed),
+		misses: MISSES.load(Ordering::Relaxed),
+		skipped_writes: SKIPPED_WRITES.load(Ordering::Relaxed),
+		entries: CACHE.lock().unwrap().entries.len(),
+	}
+}
@@ -1,15 +1,23 @@
 // Prevents additional console window on Windows in release.
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod animation;
 mod application_watcher;
 mod device_sleep;
 mod elgato;
 mod encoder_layouts;
 mod events;
+mod hotplug;
+mod http_api;
+mod image_cache;
+mod mqtt;
 mod plugins;
 mod power_events;
+mod schedules;
 mod shared;
 mod store;
+mod variables;
+mod virtual_device;
 mod zip_extract;
 
 mod built_info {
@@ -78,19 +86,33 @@ async fn main() {
 			frontend::get_applications,
 			frontend::get_application_profiles,
 			frontend::set_application_profiles,
+			frontend::get_schedules,
+			frontend::set_schedules,
 			frontend::get_fonts,
+			frontend::get_image_cache_stats,
+			frontend::variables::get_variables,
+			frontend::variables::set_variable,
+			frontend::variables::resolve_title,
 			frontend::instances::create_instance,
 			frontend::instances::move_instance,
 			frontend::instances::remove_instance,
 			frontend::instances::set_state,
 			frontend::instances::set_child_delay,
+			frontend::instances::set_child_step,
+			frontend::instances::set_multi_action_trigger,
+			frontend::instances::set_child_gesture,
+			frontend::instances::set_gesture_thresholds,
 			frontend::instances::update_image,
 			frontend::instances::trigger_virtual_press,
+			frontend::history::undo,
+			frontend::history::redo,
 			frontend::profiles::get_profiles,
 			frontend::profiles::get_selected_profile,
 			frontend::profiles::set_selected_profile,
 			frontend::profiles::delete_profile,
 			frontend::profiles::rename_profile,
+			frontend::profiles::import_streamdeck_profile,
+			frontend::profiles::export_streamdeck_profile,
 			frontend::property_inspector::make_info,
 			frontend::property_inspector::switch_property_inspector,
 			frontend::property_inspector::open_url,
@@ -98,14 +120,23 @@ async fn main() {
 			frontend::plugins::install_plugin,
 			frontend::plugins::remove_plugin,
 			frontend::plugins::reload_plugin,
+			frontend::plugins::validate_plugin_manifest,
 			frontend::plugins::show_settings_interface,
+			frontend::plugins::get_plugin_queue_depths,
 			frontend::settings::get_settings,
 			frontend::settings::set_settings,
+			frontend::settings::generate_api_token,
+			frontend::settings::get_device_settings,
+			frontend::settings::set_device_settings,
 			frontend::settings::open_config_directory,
 			frontend::settings::open_log_directory,
 			frontend::settings::get_build_info,
 			frontend::settings::backup_config_directory,
 			frontend::settings::restore_config_directory,
+			frontend::virtual_devices::get_virtual_devices,
+			frontend::virtual_devices::set_virtual_devices,
+			frontend::virtual_devices::get_virtual_device_state,
+			frontend::virtual_devices::send_virtual_device_input,
 		])
 		.setup(|app| {
 			APP_HANDLE.set(app.handle().clone()).unwrap();
@@ -193,10 +224,12 @@ If you have already donated, thank you so much for your support!"#,
 			)?;
 			let _ = app.track_event("app_started", None);
 
-			tokio::spawn(async {
+			// Polling is kept as a fallback for platforms and environments without hotplug events.
+			let poll_interval = if hotplug::init_hotplug() { 60 } else { 10 };
+			tokio::spawn(async move {
 				loop {
 					elgato::initialise_devices().await;
-					tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+					tokio::time::sleep(std::time::Duration::from_secs(poll_interval)).await;
 				}
 			});
 
@@ -206,11 +239,19 @@ If you have already donated, thank you so much for your support!"#,
 					if let Err(error) = store::profiles::flush_stale_profiles().await {
 						log::error!("Failed to flush stale profiles: {error}");
 					}
+					if let Err(error) = variables::flush().await {
+						log::error!("Failed to save variables: {error}");
+					}
 				}
 			});
 
 			plugins::initialise_plugins();
+			http_api::update_api(&settings.value);
+			mqtt::update_mqtt(&settings.value);
+			plugins::hot_reload::update_hot_reload(&settings.value);
+			virtual_device::initialise_virtual_devices();
 			application_watcher::init_application_watcher();
+			variables::init_variables();
 			device_sleep::init_device_sleep();
 			power_events::init_power_events();
 
@@ -353,6 +394,16 @@ If you have already donated, thank you so much for your support!"#,
 								}
 							});
 						}
+					} else if let Some(pos) = args.iter().position(|x| x.to_lowercase().trim() == "--set-variable") {
+						if args.len() > pos + 1 {
+							let name = args[pos + 1].clone();
+							let value = args.get(pos + 2).cloned();
+							std::thread::spawn(move || {
+								if let Err(error) = tauri::async_runtime::block_on(variables::set_variable(&name, value, None, None)) {
+									log::error!("Failed to set variable: {error}");
+								}
+							});
+						}
 					} else if let Some(pos) = args.iter().position(|x| x.to_lowercase().trim() == "--process-message") {
 						if args.len() > pos + 1 {
 							let message = args[pos + 1].clone();
@@ -399,6 +450,9 @@ If you have already donated, thank you so much for your support!"#,
 				Ok(_) => log::info!("Successfully flushed all stale profiles on exit"),
 				Err(error) => log::error!("Failed to flush stale profiles on exit: {error}"),
 			}
+			if let Err(error) = futures::executor::block_on(variables::flush()) {
+				log::error!("Failed to save variables on exit: {error}");
+			}
 
 			tokio::spawn(elgato::reset_devices());
 			use tauri_plugin_aptabase::EventTracker;
@@ -0,0 +1,171 @@
+//! An optional MQTT bridge that publishes device input and state, and accepts commands to control profiles and instances.
+
+use crate::events::inbound::ContextAndPayloadEvent;
+use crate::events::inbound::misc::SwitchProfileEvent;
+use crate::shared::{DEVICES, DeviceInfo};
+use crate::store::Settings;
+
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use rumqttc::{AsyncClient, Event, EventLoop, LastWill, MqttOptions, Packet, QoS};
+use serde_json::{Value, json};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Clone, PartialEq)]
+struct Config {
+	host: String,
+	port: u16,
+	username: String,
+	password: String,
+	prefix: String,
+}
+
+struct Bridge {
+	config: Config,
+	client: AsyncClient,
+	task: tokio::task::JoinHandle<()>,
+}
+
+static BRIDGE: LazyLock<Mutex<Option<Bridge>>> = LazyLock::new(|| Mutex::new(None));
+
+/// Generate a client ID that is unique to this connection, so that multiple instances can share a broker.
+fn client_id() -> String {
+	use rand::{Rng, distr::Alphanumeric};
+	let suffix = rand::rng().sample_iter(&Alphanumeric).take(8).map(char::from).collect::<String>();
+	format!("{}-{suffix}", crate::shared::PRODUCT_NAME.to_lowercase())
+}
+
+/// Connect, disconnect or reconnect the bridge to match the current settings.
+pub fn update_mqtt(settings: &Settings) {
+	let config = settings.mqtt_enabled.then(|| Config {
+		host: settings.mqtt_host.clone(),
+		port: settings.mqtt_port,
+		username: settings.mqtt_username.clone(),
+		password: settings.mqtt_password.clone(),
+		prefix: settings.mqtt_topic_prefix.trim_end_matches('/').to_owned(),
+	});
+
+	let mut bridge = BRIDGE.lock().unwrap();
+	if bridge.as_ref().map(|v| &v.config) == config.as_ref() {
+		return;
+	}
+
+	if let Some(old) = bridge.take() {
+		let _ = old.client.try_disconnect();
+		old.task.abort();
+	}
+	let Some(config) = config else { return };
+
+	let mut options = MqttOptions::new(client_id(), &config.host, config.port);
+	options.set_keep_alive(Duration::from_secs(30));
+	options.set_last_will(LastWill::new(format!("{}/status", config.prefix), "offline", QoS::AtLeastOnce, true));
+	if !config.username.is_empty() {
+		options.set_credentials(&config.username, &config.password);
+	}
+
+	let (client, eventloop) = AsyncClient::new(options, 64);
+	let task = tokio::spawn(run(eventloop, client.clone(), config.prefix.clone()));
+	*bridge = Some(Bridge { config, client, task });
+}
+
+async fn run(mut eventloop: EventLoop, client: AsyncClient, prefix: String) {
+	loop {
+		match eventloop.poll().await {
+			Ok(Event::Incoming(Packet::ConnAck(_))) => {
+				log::info!("Connected to MQTT broker");
+				let _ = client.try_subscribe(format!("{prefix}/devices/+/+/set"), QoS::AtLeastOnce);
+				let _ = client.try_subscribe(format!("{prefix}/contexts/+/set"), QoS::AtLeastOnce);
+				let _ = client.try_publish(format!("{prefix}/status"), QoS::AtLeastOnce, true, "online");
+				tokio::spawn(publish_all_devices());
+			}
+			Ok(Event::Incoming(Packet::Publish(publish))) => {
+				let Some(topic) = publish.topic.strip_prefix(&format!("{prefix}/")).map(|v| v.to_owned()) else {
+					continue;
+				};
+				tokio::spawn(async move {
+					if let Err(error) = handle_command(&topic, &publish.payload).await {
+						log::warn!("Failed to process MQTT command on {topic}: {error:#}");
+					}
+				});
+			}
+			Ok(_) => {}
+			Err(error) => {
+				log::warn!("MQTT connection error: {error}");
+				tokio::time::sleep(RECONNECT_DELAY).await;
+			}
+		}
+	}
+}
+
+async fn handle_command(topic: &str, payload: &[u8]) -> Result<(), anyhow::Error> {
+	let segments = topic.split('/').collect::<Vec<_>>();
+	match segments.as_slice() {
+		["devices", device, "profile", "set"] => {
+			let profile = std::str::from_utf8(payload)?.trim().to_owned();
+			if !DEVICES.contains_key(*device) || !crate::store::profiles::get_device_profiles(device)?.contains(&profile) {
+				return Err(anyhow::anyhow!("profile {profile} not found on device {device}"));
+			}
+			crate::events::inbound::misc::switch_profile(SwitchProfileEvent {
+				device: (*device).to_owned(),
+				profile,
+			})
+			.await
+		}
+		["devices", device, "brightness", "set"] => {
+			let brightness: u8 = std::str::from_utf8(payload)?.trim().parse()?;
+			if !DEVICES.contains_key(*device) {
+				return Err(anyhow::anyhow!("device {device} not found"));
+			}
+			// The brightness is only applied until it is next changed, and is not applied to sleeping devices so that they stay dark.
+			crate::events::outbound::devices::set_device_brightness(device, brightness.min(100)).await
+		}
+		["contexts", "title", "set"] => {
+			let event: ContextAndPayloadEvent<crate::events::inbound::states::SetTitlePayload> = serde_json::from_slice(payload)?;
+			crate::events::inbound::states::set_title(event).await
+		}
+		["contexts", "image", "set"] => {
+			let event: ContextAndPayloadEvent<crate::events::inbound::states::SetImagePayload> = serde_json::from_slice(payload)?;
+			crate::events::inbound::states::set_image(event).await
+		}
+		_ => Err(anyhow::anyhow!("unknown command topic")),
+	}
+}
+
+/// Publish a message under the configured topic prefix, if the bridge is enabled.
+pub fn publish(topic: &str, payload: impl Into<Vec<u8>>, retain: bool) {
+	let bridge = BRIDGE.lock().unwrap();
+	let Some(bridge) = bridge.as_ref() else { return };
+	if let Err(error) = bridge.client.try_publish(format!("{}/{topic}", bridge.config.prefix), QoS::AtLeastOnce, retain, payload) {
+		log::debug!("Failed to publish MQTT message on {topic}: {error}");
+	}
+}
+
+/// Publish an input event from a key, dial or touch strip.
+pub fn publish_input(device: &str, input: &str, position: u8, payload: Value) {
+	publish(&format!("devices/{device}/{input}/{position}"), payload.to_string(), false);
+}
+
+pub fn publish_device_connected(device: &DeviceInfo) {
+	publish(&format!("devices/{}/info", device.id), json!(device).to_string(), true);
+	publish(&format!("devices/{}/status", device.id), "online", true);
+}
+
+pub fn publish_device_disconnected(device: &str) {
+	publish(&format!("devices/{device}/status"), "offline", true);
+}
+
+pub fn publish_profile(device: &str, profile: &str) {
+	publish(&format!("devices/{device}/profile"), profile, true);
+}
+
+async fn publish_all_devices() {
+	let devices = DEVICES.iter().map(|v| v.value().clone()).collect::<Vec<_>>();
+	for device in devices {
+		publish_device_connected(&device);
+		if let Ok(profile) = crate::store::profiles::DEVICE_STORES.write().await.get_selected_profile(&device.id) {
+			publish_profile(&device.id, &profile);
+		}
+	}
+}
@@ -0,0 +1,256 @@
+//! Automatic reloading of plugins when their files change, enabled in developer mode.
+
+use crate::APP_HANDLE;
+use crate::shared::config_dir;
+use crate::store::Settings;
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use tauri::{Emitter, Manager};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// How long a plugin's files must remain unchanged before it is reloaded, so that a build writing many files only causes one reload.
+const DEBOUNCE: Duration = Duration::from_millis(750);
+
+/// Directories whose contents change without the plugin being rebuilt.
+const IGNORED_DIRECTORIES: &[&str] = &[".git", "node_modules", "__pycache__", "wineprefix"];
+
+/// Extensions of the code and asset files that a plugin is built from, as plugins may write other files such as logs, settings and caches into their own directory while running.
+/// Files without an extension are also treated as code, as they are usually native executables or the plugin directory itself.
+const RELOADED_EXTENSIONS: &[&str] = &[
+	"js", "mjs", "cjs", "wasm", "node", "html", "htm", "css", "py", "sh", "exe", "dll", "so", "dylib", "png", "svg", "jpg", "jpeg", "gif", "webp",
+];
+
+struct HotReload {
+	watcher: RecommendedWatcher,
+	/// The resolved directory of each watched plugin, so that symlinked development checkouts are watched at their real location.
+	roots: HashMap<PathBuf, String>,
+	/// Each directory being watched, as plugin directories are watched one directory at a time so that ignored directories are not watched at all.
+	watched: HashSet<PathBuf>,
+	task: tokio::task::JoinHandle<()>,
+}
+
+static HOT_RELOAD: LazyLock<Mutex<Option<HotReload>>> = LazyLock::new(|| Mutex::new(None));
+
+#[derive(Clone, Serialize)]
+struct ManifestErrorEvent {
+	plugin: String,
+	error: String,
+}
+
+/// Start or stop watching plugin directories to match the current settings.
+pub fn update_hot_reload(settings: &Settings) {
+	let mut hot_reload = HOT_RELOAD.lock().unwrap();
+	if settings.developer == hot_reload.is_some() {
+		return;
+	}
+
+	if let Some(old) = hot_reload.take() {
+		old.task.abort();
+		return;
+	}
+
+	let (tx, rx) = mpsc::unbounded_channel();
+	let watcher = notify::recommended_watcher(move |result: notify::Result<Event>| match result {
+		// Files and directories being opened, including by the plugin itself and by walking its directory to watch it, are not changes.
+		Ok(event) if event.kind.is_access() => {}
+		Ok(event) => {
+			for path in event.paths {
+				let _ = tx.send(path);
+			}
+		}
+		Err(error) => log::warn!("Plugin file watcher error: {error}"),
+	});
+	let mut watcher = match watcher {
+		Ok(watcher) => watcher,
+		Err(error) => {
+			log::error!("Failed to start watching plugin directories: {error}");
+			return;
+		}
+	};
+
+	let plugins_dir = config_dir().join("plugins");
+	if let Err(error) = watcher.watch(&plugins_dir, RecursiveMode::NonRecursive) {
+		log::warn!("Failed to watch plugins directory: {error}");
+	}
+
+	let mut state = HotReload {
+		watcher,
+		roots: HashMap::new(),
+		watched: HashSet::new(),
+		task: tokio::spawn(run(rx)),
+	};
+	refresh_watches(&mut state, None);
+	*hot_reload = Some(state);
+}
+
+/// Watch each plugin directory that is not already watched, and stop watching those that no longer exist.
+///
+/// `replaced` is a directory that has been recreated, such as by a build, and so must be watched again even if it was already watched.
+fn refresh_watches(state: &mut HotReload, replaced: Option<&Path>) {
+	let mut roots = HashMap::new();
+	if let Ok(entries) = std::fs::read_dir(config_dir().join("plugins")) {
+		for entry in entries.flatten() {
+			let Ok(root) = entry.path().canonicalize() else { continue };
+			if root.is_dir() {
+				roots.insert(root, entry.file_name().to_string_lossy().into_owned());
+			}
+		}
+	}
+
+	let removed = state.roots.keys().filter(|root| !roots.contains_key(*root)).cloned().collect::<Vec<_>>();
+	for old in removed {
+		unwatch_tree(state, &old);
+	}
+	for root in roots.keys() {
+		if state.roots.contains_key(root) {
+			if replaced != Some(root.as_path()) {
+				continue;
+			}
+			unwatch_tree(state, root);
+		}
+		watch_tree(state, root);
+	}
+	state.roots = roots;
+}
+
+/// Watch a directory and each of its subdirectories that is not ignored, without following symbolic links.
+fn watch_tree(state: &mut HotReload, dir: &Path) {
+	let mut stack = vec![dir.to_path_buf()];
+	while let Some(dir) = stack.pop() {
+		if state.watched.contains(&dir) {
+			let _ = state.watcher.unwatch(&dir);
+		}
+		if let Err(error) = state.watcher.watch(&dir, RecursiveMode::NonRecursive) {
+			log::warn!("Failed to watch plugin directory {}: {error}", dir.display());
+			continue;
+		}
+		state.watched.insert(dir.clone());
+
+		let Ok(entries) = std::fs::read_dir(&dir) else { continue };
+		for entry in entries.flatten() {
+			if entry.file_type().is_ok_and(|v| v.is_dir()) && !IGNORED_DIRECTORIES.contains(&entry.file_name().to_string_lossy().as_ref()) {
+				stack.push(entry.path());
+			}
+		}
+	}
+}
+
+/// Stop watching a directory and all of its subdirectories.
+fn unwatch_tree(state: &mut HotReload, dir: &Path) {
+	let watched = state.watched.iter().filter(|v| v.starts_with(dir)).cloned().collect::<Vec<_>>();
+	for watched in watched {
+		let _ = state.watcher.unwatch(&watched);
+		state.watched.remove(&watched);
+	}
+}
+
+/// Find the directory and UUID of the plugin that a path belongs to, if it is not in an ignored directory.
+fn plugin_root<'a>(roots: &'a HashMap<PathBuf, String>, path: &Path) -> Option<(&'a PathBuf, &'a String)> {
+	let (root, uuid) = roots.iter().find(|(root, _)| path.starts_with(root))?;
+	let relative = path.strip_prefix(root).ok()?;
+	if relative.components().any(|component| IGNORED_DIRECTORIES.contains(&component.as_os_str().to_string_lossy().as_ref())) {
+		return None;
+	}
+	Some((root, uuid))
+}
+
+/// Find the plugin that a changed path belongs to, if it is a code or asset file that is not in an ignored directory.
+fn plugin_for_path(roots: &HashMap<PathBuf, String>, path: &Path) -> Option<String> {
+	let (root, uuid) = plugin_root(roots, path)?;
+	let relative = path.strip_prefix(root).ok()?;
+	let is_source = match relative.extension() {
+		Some(extension) => RELOADED_EXTENSIONS.iter().any(|reloaded| extension.eq_ignore_ascii_case(reloaded)),
+		None => true,
+	};
+	if !is_source && relative != Path::new("manifest.json") {
+		return None;
+	}
+	Some(uuid.clone())
+}
+
+async fn run(mut rx: mpsc::UnboundedReceiver<PathBuf>) {
+	let plugins_dir = config_dir().join("plugins");
+	let mut pending: HashMap<String, Instant> = HashMap::new();
+	let mut replaced_roots: HashSet<String> = HashSet::new();
+	loop {
+		let next = pending.values().min().copied();
+		let received = match next {
+			Some(next) => tokio::select! {
+				path = rx.recv() => Some(path),
+				_ = tokio::time::sleep_until(next) => None,
+			},
+			None => Some(rx.recv().await),
+		};
+
+		if let Some(path) = received {
+			let Some(path) = path else { return };
+			let mut hot_reload = HOT_RELOAD.lock().unwrap();
+			let Some(state) = hot_reload.as_mut() else { return };
+			// Plugins being added to or removed from the plugins directory change which directories need to be watched.
+			if path.parent() == Some(plugins_dir.as_path()) {
+				let replaced = path.canonicalize().ok();
+				refresh_watches(state, replaced.as_deref());
+			}
+			// Directories created within a plugin, such as build output directories, are watched as they appear.
+			if path.is_dir()
+				&& let Some((root, _)) = plugin_root(&state.roots, &path)
+				&& *root != path
+			{
+				watch_tree(state, &path);
+			}
+			if let Some(uuid) = plugin_for_path(&state.roots, &path) {
+				// A plugin directory that is replaced by a build loses its watch, so it is watched again once the build has finished.
+				if state.roots.contains_key(&path) {
+					replaced_roots.insert(uuid.clone());
+				}
+				pending.insert(uuid, Instant::now() + DEBOUNCE);
+			}
+			continue;
+		}
+
+		let now = Instant::now();
+		let due = pending.iter().filter(|(_, due)| **due <= now).map(|(uuid, _)| uuid.clone()).collect::<Vec<_>>();
+		for uuid in due {
+			pending.remove(&uuid);
+			if replaced_roots.remove(&uuid)
+				&& let Some(state) = HOT_RELOAD.lock().unwrap().as_mut()
+				&& let Some(root) = state.roots.iter().find(|(_, v)| **v == uuid).map(|(root, _)| root.clone())
+			{
+				refresh_watches(state, Some(&root));
+			}
+			reload(uuid).await;
+		}
+	}
+}
+
+async fn reload(uuid: String) {
+	let path = config_dir().join("plugins").join(&uuid);
+	if !path.exists() {
+		return;
+	}
+	let app = APP_HANDLE.get().unwrap();
+
+	if let Err(error) = super::manifest::read_manifest(&path) {
+		log::warn!("Not reloading plugin {uuid} due to invalid manifest: {error:#}");
+		if let Some(window) = app.get_webview_window("main") {
+			let _ = window.emit(
+				"plugin_manifest_error",
+				ManifestErrorEvent {
+					plugin: uuid,
+					error: format!("{error:#}"),
+				},
+			);
+		}
+		return;
+	}
+
+	log::info!("Reloading plugin {uuid} after its files changed");
+	crate::events::frontend::plugins::reload_plugin(app.clone(), uuid).await;
+}
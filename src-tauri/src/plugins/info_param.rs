@@ -16,6 +16,8 @@ pub struct ApplicationInfo {
 pub struct PluginInfo {
 	pub uuid: String,
 	pub version: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub token: Option<String>,
 }
 
 #[allow(non_snake_case)]
@@ -67,8 +69,8 @@ pub struct Info {
 	pub devices: Vec<DeviceInfo>,
 }
 
-/// Construct the info parameter for a given plugin's UUID and version.
-pub async fn make_info(uuid: String, version: String, wine: bool) -> Info {
+/// Construct the info parameter for a given plugin's UUID and version, optionally including its registration token.
+pub async fn make_info(uuid: String, version: String, wine: bool, token: Option<String>) -> Info {
 	let configured_language = crate::store::get_settings().value.language;
 
 	#[cfg(target_os = "windows")]
@@ -90,7 +92,7 @@ pub async fn make_info(uuid: String, version: String, wine: bool) -> Info {
 			platformVersion: if !wine { os_info::get().version().to_string() } else { "10.0.19045.4474".to_owned() },
 			version: "7.1.0".to_owned(),
 		},
-		plugin: PluginInfo { uuid, version },
+		plugin: PluginInfo { uuid, version, token },
 		devicePixelRatio: 0,
 		colors: ColoursInfo {
 			buttonPressedBackgroundColor: "#303030FF".to_owned(),
@@ -0,0 +1,99 @@
+//! Verification of plugin archives against expected checksums and the signatures of trusted publishers before they are installed.
+
+use crate::shared::config_dir;
+use crate::store::{NotProfile, Store};
+
+use anyhow::anyhow;
+use base64::Engine;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// A record of where an installed plugin came from and how its archive was verified.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InstallRecord {
+	pub source: Option<String>,
+	/// The SHA-256 hash of the installed archive, as lowercase hexadecimal.
+	pub sha256: String,
+	/// Whether the hash matched one provided when the plugin was installed.
+	pub checksum_verified: bool,
+	/// The trusted publisher key that the archive was signed with, if it was signed.
+	pub signed_by: Option<String>,
+	pub installed_at: u64,
+}
+
+impl NotProfile for InstallRecord {}
+
+/// Check an archive against an expected SHA-256 hash and an ed25519 signature, either of which may be omitted.
+///
+/// The hash may be given as hexadecimal with an optional `sha256:` prefix, and the signature, which covers the archive itself, as base64.
+/// Signatures are checked against the base64-encoded public keys in `Settings::trusted_plugin_keys`.
+pub fn verify(bytes: &[u8], expected_sha256: Option<&str>, signature: Option<&str>) -> Result<InstallRecord, anyhow::Error> {
+	let sha256 = format!("{:x}", Sha256::digest(bytes));
+
+	let expected_sha256 = expected_sha256.map(|v| v.trim()).filter(|v| !v.is_empty());
+	if let Some(expected) = expected_sha256 {
+		let expected = expected.strip_prefix("sha256:").unwrap_or(expected).to_lowercase();
+		if expected != sha256 {
+			return Err(anyhow!("checksum mismatch: expected SHA-256 {expected} but the downloaded archive has {sha256}"));
+		}
+	}
+
+	let signed_by = match signature.map(|v| v.trim()).filter(|v| !v.is_empty()) {
+		Some(signature) => Some(verify_signature(bytes, signature)?),
+		None => None,
+	};
+
+	Ok(InstallRecord {
+		sha256,
+		checksum_verified: expected_sha256.is_some(),
+		signed_by,
+		..Default::default()
+	})
+}
+
+fn verify_signature(bytes: &[u8], signature: &str) -> Result<String, anyhow::Error> {
+	let engine = base64::engine::general_purpose::STANDARD;
+	let signature: [u8; 64] = engine
+		.decode(signature)
+		.ok()
+		.and_then(|v| v.try_into().ok())
+		.ok_or_else(|| anyhow!("signature is not a base64-encoded ed25519 signature"))?;
+	let signature = Signature::from_bytes(&signature);
+
+	let trusted = crate::store::get_settings().value.trusted_plugin_keys;
+	if trusted.is_empty() {
+		return Err(anyhow!("the plugin is signed, but no trusted publisher keys are configured to verify it with"));
+	}
+
+	for key in trusted {
+		let Some(verifying_key) = engine
+			.decode(key.trim())
+			.ok()
+			.and_then(|v| <[u8; 32]>::try_from(v).ok())
+			.and_then(|v| VerifyingKey::from_bytes(&v).ok())
+		else {
+			log::warn!("Ignoring invalid trusted publisher key {key}");
+			continue;
+		};
+		if verifying_key.verify(bytes, &signature).is_ok() {
+			return Ok(key);
+		}
+	}
+
+	Err(anyhow!("the signature does not match any trusted publisher key"))
+}
+
+/// Save the install record of a plugin, replacing any from a previous install.
+pub fn save_record(id: &str, mut record: InstallRecord, source: Option<String>) -> Result<(), anyhow::Error> {
+	record.source = source;
+	record.installed_at = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|v| v.as_secs()).unwrap_or_default();
+	let mut store = Store::new(id, &config_dir().join("install_records"), InstallRecord::default())?;
+	store.value = record;
+	store.save()
+}
+
+pub fn remove_record(id: &str) {
+	let _ = std::fs::remove_file(config_dir().join("install_records").join(format!("{id}.json")));
+}
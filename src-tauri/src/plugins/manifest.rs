@@ -68,7 +68,8 @@ pub struct PluginManifest {
 	pub has_settings_interface: Option<bool>,
 }
 
-pub fn read_manifest(base_path: &std::path::Path) -> Result<PluginManifest, anyhow::Error> {
+/// Read a plugin's manifest as JSON, with any overrides for the current platform applied.
+pub fn read_manifest_value(base_path: &std::path::Path) -> Result<serde_json::Value, anyhow::Error> {
 	use anyhow::Context;
 
 	let mut manifest: serde_json::Value = serde_json::from_str(
@@ -85,5 +86,11 @@ pub fn read_manifest(base_path: &std::path::Path) -> Result<PluginManifest, anyh
 		json_patch::merge(&mut manifest, &platform_overrides);
 	}
 
-	serde_json::from_value(manifest).context("failed to parse manifest")
+	Ok(manifest)
+}
+
+pub fn read_manifest(base_path: &std::path::Path) -> Result<PluginManifest, anyhow::Error> {
+	use anyhow::Context;
+
+	serde_json::from_value(read_manifest_value(base_path)?).context("failed to parse manifest")
 }
@@ -1,5 +1,11 @@
+pub mod hot_reload;
 pub mod info_param;
+pub mod integrity;
 pub mod manifest;
+pub mod python;
+pub mod sandbox;
+pub mod supervisor;
+pub mod validation;
 mod webserver;
 
 use crate::APP_HANDLE;
@@ -13,6 +19,7 @@ use std::{fs, path};
 
 use tauri::{AppHandle, Manager};
 
+use dashmap::DashMap;
 use futures::StreamExt;
 use tokio::net::{TcpListener, TcpStream};
 
@@ -24,6 +31,7 @@ pub enum PluginChildType {
 	Wine,
 	Native,
 	Node,
+	Python,
 }
 
 enum PluginInstance {
@@ -31,16 +39,23 @@ enum PluginInstance {
 	Wine(Child),
 	Native(Child),
 	Node(Child),
+	Python(Child),
 }
 
 pub static DEVICE_NAMESPACES: LazyLock<RwLock<HashMap<String, String>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
 static INSTANCES: LazyLock<Mutex<HashMap<String, PluginInstance>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+static PLUGIN_TOKENS: LazyLock<DashMap<String, String>> = LazyLock::new(DashMap::new);
+
+/// Get the address that the servers plugins communicate with should bind to.
+pub fn bind_address() -> &'static str {
+	if crate::store::get_settings().value.loopback_only { "127.0.0.1" } else { "0.0.0.0" }
+}
 
 pub static PORT_BASE: LazyLock<u16> = LazyLock::new(|| {
 	let mut base = 57116;
 	loop {
-		let websocket_result = std::net::TcpListener::bind(format!("0.0.0.0:{}", base));
-		let webserver_result = std::net::TcpListener::bind(format!("0.0.0.0:{}", base + 2));
+		let websocket_result = std::net::TcpListener::bind(format!("{}:{}", bind_address(), base));
+		let webserver_result = std::net::TcpListener::bind(format!("{}:{}", bind_address(), base + 2));
 		if websocket_result.is_ok() && webserver_result.is_ok() {
 			log::debug!("Using ports {} and {}", base, base + 2);
 			break;
@@ -65,6 +80,19 @@ fn attach_parent_death_signal(command: &mut Command) {
 	}
 }
 
+/// Generate a registration token for a plugin, replacing any token issued during a previous launch.
+fn issue_token(uuid: &str) -> String {
+	use rand::{Rng, distr::Alphanumeric};
+	let token = rand::rng().sample_iter(&Alphanumeric).take(32).map(char::from).collect::<String>();
+	PLUGIN_TOKENS.insert(uuid.to_owned(), token.clone());
+	token
+}
+
+/// Get the registration token issued to a plugin during its current launch.
+pub fn expected_token(uuid: &str) -> Option<String> {
+	PLUGIN_TOKENS.get(uuid).map(|token| token.clone())
+}
+
 pub type SpawnRequest = Box<dyn FnOnce() -> Result<(String, PluginChildType, Command), anyhow::Error> + Send>;
 
 /// Initialise a plugin from a given directory.
@@ -201,6 +229,7 @@ pub async fn initialise_plugin(path: path::PathBuf, spawner_tx: mpsc::Sender<Spa
 	}
 
 	let code_path = code_path.unwrap();
+	let token = issue_token(&plugin_uuid);
 	let args = [
 		"-port".to_owned(),
 		PORT_BASE.to_string(),
@@ -223,7 +252,7 @@ pub async fn initialise_plugin(path: path::PathBuf, spawner_tx: mpsc::Sender<Spa
 			window.open_devtools();
 		}
 
-		let info = info_param::make_info(plugin_uuid.to_owned(), manifest.version, false).await;
+		let info = info_param::make_info(plugin_uuid.to_owned(), manifest.version, false, Some(token)).await;
 		window.eval(format!(
 			r#"const opendeckInit = () => {{
 				try {{
@@ -252,12 +281,12 @@ pub async fn initialise_plugin(path: path::PathBuf, spawner_tx: mpsc::Sender<Spa
 			return Err(anyhow!("Node.js version 20.0.0 or higher is required"));
 		}
 
-		let info = info_param::make_info(plugin_uuid.to_owned(), manifest.version, true).await;
+		let info = info_param::make_info(plugin_uuid.to_owned(), manifest.version, true, Some(token)).await;
 		let log_file = fs::File::create(log_dir().join("plugins").join(format!("{plugin_uuid}.log")))?;
 
 		spawner_tx
 			.send(Box::new(move || {
-				let mut command = Command::new(command);
+				let mut command = sandbox::command(command, &plugin_uuid, &path)?;
 				command
 					.current_dir(path)
 					.args(extra_args)
@@ -276,6 +305,47 @@ pub async fn initialise_plugin(path: path::PathBuf, spawner_tx: mpsc::Sender<Spa
 				Ok((plugin_uuid, PluginChildType::Node, command))
 			}))
 			.map_err(|e| anyhow!(e.to_string()))?;
+	} else if code_path.to_lowercase().ends_with(".py") {
+		let interpreter = python::find_interpreter()?;
+		let info = info_param::make_info(plugin_uuid.to_owned(), manifest.version, false, Some(token.clone())).await;
+		let log_file = fs::File::create(log_dir().join("plugins").join(format!("{plugin_uuid}.log")))?;
+
+		// Installing dependencies can take minutes, so the plugin is started once they are ready instead of holding up initialisation.
+		tokio::task::spawn_blocking(move || {
+			let interpreter = match python::prepare(&plugin_uuid, &path, interpreter, &log_file) {
+				Ok(interpreter) => interpreter,
+				Err(error) => {
+					warn!("Failed to prepare Python environment for plugin {}: {:#}", plugin_uuid, error);
+					return;
+				}
+			};
+			// Skip starting the plugin if it was deactivated or initialised again while its dependencies were being installed.
+			if expected_token(&plugin_uuid).as_deref() != Some(token.as_str()) {
+				return;
+			}
+
+			let request: SpawnRequest = Box::new(move || {
+				let mut command = sandbox::command(&interpreter.program, &plugin_uuid, &path)?;
+				command
+					.current_dir(&path)
+					.args(interpreter.args)
+					.arg("-u")
+					.arg(path.join(code_path))
+					.args(args)
+					.arg(serde_json::to_string(&info)?)
+					.stdout(Stdio::from(log_file.try_clone()?))
+					.stderr(Stdio::from(log_file));
+				#[cfg(target_os = "linux")]
+				attach_parent_death_signal(&mut command);
+				#[cfg(target_os = "windows")]
+				{
+					use std::os::windows::process::CommandExt;
+					command.creation_flags(0x08000000);
+				}
+				Ok((plugin_uuid, PluginChildType::Python, command))
+			});
+			let _ = spawner_tx.send(request);
+		});
 	} else if use_wine {
 		let command = if is_flatpak() { "flatpak-spawn" } else { "wine" };
 		let extra_args = if is_flatpak() { vec!["--host", "wine"] } else { vec![] };
@@ -291,12 +361,12 @@ pub async fn initialise_plugin(path: path::PathBuf, spawner_tx: mpsc::Sender<Spa
 			return Err(anyhow!("failed to detect an installation of Wine"));
 		}
 
-		let info = info_param::make_info(plugin_uuid.to_owned(), manifest.version, true).await;
+		let info = info_param::make_info(plugin_uuid.to_owned(), manifest.version, true, Some(token)).await;
 		let log_file = fs::File::create(log_dir().join("plugins").join(format!("{plugin_uuid}.log")))?;
 
 		spawner_tx
 			.send(Box::new(move || {
-				let mut command = Command::new(command);
+				let mut command = sandbox::command(command, &plugin_uuid, &path)?;
 				command
 					.current_dir(&path)
 					.args(extra_args)
@@ -316,7 +386,7 @@ pub async fn initialise_plugin(path: path::PathBuf, spawner_tx: mpsc::Sender<Spa
 			}))
 			.map_err(|e| anyhow!(e.to_string()))?;
 	} else {
-		let info = info_param::make_info(plugin_uuid.to_owned(), manifest.version, false).await;
+		let info = info_param::make_info(plugin_uuid.to_owned(), manifest.version, false, Some(token)).await;
 		let log_file = fs::File::create(log_dir().join("plugins").join(format!("{plugin_uuid}.log")))?;
 
 		#[cfg(unix)]
@@ -327,7 +397,7 @@ pub async fn initialise_plugin(path: path::PathBuf, spawner_tx: mpsc::Sender<Spa
 
 		spawner_tx
 			.send(Box::new(move || {
-				let mut command = Command::new(path.join(code_path));
+				let mut command = sandbox::command(path.join(code_path), &plugin_uuid, &path)?;
 				command
 					.current_dir(path)
 					.args(args)
@@ -356,6 +426,8 @@ pub async fn initialise_plugin(path: path::PathBuf, spawner_tx: mpsc::Sender<Spa
 }
 
 pub async fn deactivate_plugin(app: &AppHandle, uuid: &str) -> Result<(), anyhow::Error> {
+	PLUGIN_TOKENS.remove(uuid);
+
 	{
 		let mut namespaces = DEVICE_NAMESPACES.write().await;
 		if let Some((namespace, _)) = namespaces.clone().iter().find(|(_, plugin)| uuid == **plugin) {
@@ -379,7 +451,7 @@ pub async fn deactivate_plugin(app: &AppHandle, uuid: &str) -> Result<(), anyhow
 					tokio::time::sleep(std::time::Duration::from_millis(10)).await;
 				}
 			}
-			PluginInstance::Node(mut child) | PluginInstance::Wine(mut child) | PluginInstance::Native(mut child) => {
+			PluginInstance::Node(mut child) | PluginInstance::Python(mut child) | PluginInstance::Wine(mut child) | PluginInstance::Native(mut child) => {
 				child.kill()?;
 				child.wait()?;
 			}
@@ -459,12 +531,14 @@ pub fn initialise_plugins() {
 			match f() {
 				Ok((plugin_uuid, child_type, mut command)) => match command.spawn() {
 					Ok(child) => {
+						supervisor::note_started(&plugin_uuid);
 						INSTANCES.blocking_lock().insert(
 							plugin_uuid,
 							match child_type {
 								PluginChildType::Wine => PluginInstance::Wine(child),
 								PluginChildType::Native => PluginInstance::Native(child),
 								PluginChildType::Node => PluginInstance::Node(child),
+								PluginChildType::Python => PluginInstance::Python(child),
 							},
 						);
 					}
@@ -496,6 +570,8 @@ pub fn initialise_plugins() {
 		}
 	}
 
+	supervisor::init_supervisor();
+
 	// On macOS, hidden WKWebView windows suspend JavaScript after ~7s.
 	// Periodically eval a no-op to keep them alive.
 	#[cfg(target_os = "macos")]
@@ -516,7 +592,7 @@ pub fn initialise_plugins() {
 
 /// Start the WebSocket server that plugins communicate with.
 async fn init_websocket_server() {
-	let listener = match TcpListener::bind(format!("0.0.0.0:{}", *PORT_BASE)).await {
+	let listener = match TcpListener::bind(format!("{}:{}", bind_address(), *PORT_BASE)).await {
 		Ok(listener) => listener,
 		Err(error) => {
 			error!("Failed to bind plugin WebSocket server to socket: {}", error);
@@ -550,10 +626,9 @@ async fn accept_connection(stream: TcpStream) {
 	let Ok(register_event) = socket.next().await.unwrap() else {
 		return;
 	};
-	match serde_json::from_str(&register_event.clone().into_text().unwrap()) {
+	// Connections must register before sending any other events, so that they can be authenticated.
+	match serde_json::from_str(&register_event.into_text().unwrap_or_default()) {
 		Ok(event) => crate::events::register_plugin(event, socket).await,
-		Err(_) => {
-			let _ = crate::events::inbound::process_incoming_message(Ok(register_event), "", false).await;
-		}
+		Err(_) => warn!("Rejected WebSocket connection that did not begin with a registration event"),
 	}
 }
@@ -0,0 +1,133 @@
+//! Locating a Python interpreter for plugins written in Python, and preparing virtual environments for plugins that declare dependencies.
+
+use crate::shared::is_flatpak;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::anyhow;
+
+/// The oldest version of Python that plugins can be run with.
+const MIN_VERSION: (u32, u32) = (3, 9);
+
+/// The name of the file listing a plugin's dependencies, in the format accepted by `pip install -r`.
+const REQUIREMENTS_FILE: &str = "requirements.txt";
+
+/// A command that runs a Python interpreter.
+#[derive(Clone)]
+pub struct Interpreter {
+	pub program: String,
+	pub args: Vec<String>,
+}
+
+impl Interpreter {
+	fn new(program: impl Into<String>, args: &[&str]) -> Self {
+		let program = program.into();
+		if is_flatpak() {
+			let mut host_args = vec!["--host".to_owned(), program];
+			host_args.extend(args.iter().map(|v| (*v).to_owned()));
+			Self {
+				program: "flatpak-spawn".to_owned(),
+				args: host_args,
+			}
+		} else {
+			Self {
+				program,
+				args: args.iter().map(|v| (*v).to_owned()).collect(),
+			}
+		}
+	}
+
+	pub fn command(&self) -> Command {
+		let mut command = Command::new(&self.program);
+		command.args(&self.args);
+		command
+	}
+
+	/// Create a command that runs the interpreter to install the dependencies of a plugin, restricted by the plugin's sandbox policy.
+	fn install_command(&self, uuid: &str, plugin_dir: &Path) -> Result<Command, anyhow::Error> {
+		let mut command = super::sandbox::install_command(&self.program, uuid, plugin_dir)?;
+		command.args(&self.args);
+		Ok(command)
+	}
+
+	fn version(&self) -> Option<(u32, u32)> {
+		let output = self.command().arg("--version").stdin(Stdio::null()).output().ok()?;
+		// Python 2 prints its version to stderr.
+		let text = String::from_utf8_lossy(if output.stdout.is_empty() { &output.stderr } else { &output.stdout }).to_string();
+		let mut numbers = text.trim().strip_prefix("Python ")?.split('.');
+		Some((numbers.next()?.parse().ok()?, numbers.next()?.parse().ok()?))
+	}
+}
+
+/// Find a Python interpreter that meets the minimum supported version.
+pub fn find_interpreter() -> Result<Interpreter, anyhow::Error> {
+	#[cfg(target_os = "windows")]
+	let candidates = [Interpreter::new("py", &["-3"]), Interpreter::new("python", &[]), Interpreter::new("python3", &[])];
+	#[cfg(not(target_os = "windows"))]
+	let candidates = [Interpreter::new("python3", &[]), Interpreter::new("python", &[])];
+
+	candidates
+		.into_iter()
+		.find(|interpreter| interpreter.version().is_some_and(|version| version >= MIN_VERSION))
+		.ok_or_else(|| anyhow!("Python version {}.{} or higher is required", MIN_VERSION.0, MIN_VERSION.1))
+}
+
+fn venv_python(venv: &Path) -> PathBuf {
+	if cfg!(target_os = "windows") {
+		venv.join("Scripts").join("python.exe")
+	} else {
+		venv.join("bin").join("python")
+	}
+}
+
+fn run_logged(mut command: Command, log_file: &fs::File, action: &str) -> Result<(), anyhow::Error> {
+	let status = command
+		.stdin(Stdio::null())
+		.stdout(Stdio::from(log_file.try_clone()?))
+		.stderr(Stdio::from(log_file.try_clone()?))
+		.status()?;
+	if !status.success() {
+		return Err(anyhow!("failed to {action} ({status})"));
+	}
+	Ok(())
+}
+
+/// Get the interpreter to run a plugin with, creating or updating its virtual environment if it has a requirements file.
+///
+/// The environment is kept in the plugin's data directory and is only rebuilt when the requirements file changes.
+/// Output from creating the environment and installing dependencies is written to `log_file`.
+/// Both are run with the plugin's sandbox policy, other than its TCP restrictions.
+pub fn prepare(uuid: &str, plugin_dir: &Path, interpreter: Interpreter, log_file: &fs::File) -> Result<Interpreter, anyhow::Error> {
+	let requirements = match fs::read(plugin_dir.join(REQUIREMENTS_FILE)) {
+		Ok(requirements) => requirements,
+		Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(interpreter),
+		Err(error) => return Err(error.into()),
+	};
+
+	let venv = super::sandbox::data_dir(uuid).join("venv");
+	let python = Interpreter::new(venv_python(&venv).to_string_lossy(), &[]);
+	let installed_requirements = venv.join(REQUIREMENTS_FILE);
+	if python.version().is_some() && fs::read(&installed_requirements).is_ok_and(|installed| installed == requirements) {
+		return Ok(python);
+	}
+
+	log::info!("Preparing Python environment for plugin {uuid}");
+	let _ = fs::remove_dir_all(&venv);
+	fs::create_dir_all(venv.parent().unwrap())?;
+
+	let mut create = interpreter.install_command(uuid, plugin_dir)?;
+	create.args(["-m", "venv"]).arg(&venv);
+	run_logged(create, log_file, "create virtual environment")?;
+
+	let mut install = python.install_command(uuid, plugin_dir)?;
+	install.args(["-m", "pip", "install", "--disable-pip-version-check", "-r"]).arg(plugin_dir.join(REQUIREMENTS_FILE));
+	if let Err(error) = run_logged(install, log_file, "install requirements") {
+		let _ = fs::remove_dir_all(&venv);
+		return Err(error);
+	}
+
+	fs::write(installed_requirements, requirements)?;
+	Ok(python)
+}
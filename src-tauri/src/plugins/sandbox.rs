@@ -0,0 +1,301 @@
+//! Optional restrictions on the resources that plugin processes can use, configured by per-plugin policy files.
+//!
+//! Policies are read from `sandbox/default.json` and then `sandbox/{uuid}.json` in the config directory, with fields set in the latter overriding the former.
+//! Restrictions are currently only enforced on Linux.
+//!
+//! Network restrictions only cover TCP, as Landlock cannot restrict other protocols. Plugins with `deny_tcp` set can still send and receive UDP traffic, including DNS lookups and QUIC, and connect to other local processes over Unix domain sockets.
+
+use crate::shared::config_dir;
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+#[derive(Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct SandboxPolicy {
+	/// The maximum memory the plugin may use, in mebibytes.
+	pub memory_limit_mb: Option<u64>,
+	/// The maximum CPU time the plugin may use, as a percentage of one core.
+	pub cpu_limit_percent: Option<u32>,
+	/// Prevent the plugin from writing anywhere except its own directory and its data directory.
+	pub read_only_filesystem: bool,
+	/// Prevent the plugin from making or accepting TCP connections, except to OpenDeck itself. Other protocols are not restricted.
+	#[serde(alias = "deny_network")]
+	pub deny_tcp: bool,
+}
+
+impl SandboxPolicy {
+	pub fn is_unrestricted(&self) -> bool {
+		*self == Self::default()
+	}
+}
+
+fn read_policy_file(path: &Path, merged: &mut serde_json::Map<String, Value>) -> Result<(), anyhow::Error> {
+	let contents = match std::fs::read(path) {
+		Ok(contents) => contents,
+		Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+		Err(error) => return Err(error.into()),
+	};
+	match serde_json::from_slice(&contents)? {
+		Value::Object(map) => merged.extend(map),
+		_ => return Err(anyhow::anyhow!("{} is not a JSON object", path.display())),
+	}
+	Ok(())
+}
+
+/// Load the sandbox policy of a plugin.
+pub fn load_policy(uuid: &str) -> Result<SandboxPolicy, anyhow::Error> {
+	let dir = config_dir().join("sandbox");
+	let mut merged = serde_json::Map::new();
+	read_policy_file(&dir.join("default.json"), &mut merged)?;
+	read_policy_file(&dir.join(format!("{uuid}.json")), &mut merged)?;
+	Ok(serde_json::from_value(Value::Object(merged))?)
+}
+
+/// The directory a plugin may always write to, even with a read-only filesystem.
+pub fn data_dir(uuid: &str) -> PathBuf {
+	config_dir().join("plugin_data").join(uuid)
+}
+
+/// Create the command used to start a plugin, applying its sandbox policy.
+pub fn command(program: impl AsRef<OsStr>, uuid: &str, plugin_dir: &Path) -> Result<Command, anyhow::Error> {
+	apply_policy(program, uuid, plugin_dir, load_policy(uuid)?)
+}
+
+/// Create a command used to install the dependencies of a plugin, applying its sandbox policy except for TCP restrictions so that they can be downloaded.
+pub fn install_command(program: impl AsRef<OsStr>, uuid: &str, plugin_dir: &Path) -> Result<Command, anyhow::Error> {
+	let policy = SandboxPolicy {
+		deny_tcp: false,
+		..load_policy(uuid)?
+	};
+	apply_policy(program, uuid, plugin_dir, policy)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn apply_policy(program: impl AsRef<OsStr>, uuid: &str, _plugin_dir: &Path, policy: SandboxPolicy) -> Result<Command, anyhow::Error> {
+	if !policy.is_unrestricted() {
+		log::warn!("Sandbox policy for plugin {uuid} is not enforced on this platform");
+	}
+	Ok(Command::new(program))
+}
+
+/// Apply a sandbox policy to a command run on behalf of a plugin.
+///
+/// Memory and CPU limits are enforced by starting the plugin in its own systemd scope, which uses cgroups v2.
+/// Without a systemd user instance, the memory limit falls back to limiting the data segment of the process, and CPU limits are not enforced.
+/// The address space is not limited instead, as runtimes such as Node.js reserve far more of it than they use and fail to start.
+/// Filesystem and TCP restrictions use Landlock, and the plugin is not started if they are requested but unsupported by the kernel.
+#[cfg(target_os = "linux")]
+fn apply_policy(program: impl AsRef<OsStr>, uuid: &str, plugin_dir: &Path, policy: SandboxPolicy) -> Result<Command, anyhow::Error> {
+	use std::os::unix::process::CommandExt;
+
+	if policy.is_unrestricted() {
+		return Ok(Command::new(program));
+	}
+	if program.as_ref() == "flatpak-spawn" {
+		return Err(anyhow::anyhow!("sandbox policies cannot be enforced on plugins started outside of the Flatpak sandbox"));
+	}
+
+	let limited = policy.memory_limit_mb.is_some() || policy.cpu_limit_percent.is_some();
+	let mut command = if limited && *SYSTEMD_RUN_AVAILABLE {
+		let mut command = Command::new("systemd-run");
+		command.args(["--user", "--scope", "--quiet", "--collect"]);
+		command.arg(format!("--unit=opendeck-plugin-{}-{}", uuid.replace(|c: char| !c.is_ascii_alphanumeric(), "_"), std::process::id()));
+		if let Some(memory) = policy.memory_limit_mb {
+			command.arg(format!("--property=MemoryMax={memory}M")).arg("--property=MemorySwapMax=0");
+		}
+		if let Some(cpu) = policy.cpu_limit_percent {
+			command.arg(format!("--property=CPUQuota={cpu}%"));
+		}
+		command.arg("--").arg(program);
+		command
+	} else {
+		let mut command = Command::new(program);
+		if limited {
+			log::warn!("Could not create a systemd scope for plugin {uuid}, so only its data segment can be limited");
+		}
+		if let Some(memory) = policy.memory_limit_mb {
+			let limit = libc::rlimit {
+				rlim_cur: memory.saturating_mul(1024 * 1024),
+				rlim_max: memory.saturating_mul(1024 * 1024),
+			};
+			// SAFETY: `libc::setrlimit` is async-signal-safe.
+			unsafe {
+				command.pre_exec(move || {
+					if libc::setrlimit(libc::RLIMIT_DATA, &limit) != 0 {
+						return Err(std::io::Error::last_os_error());
+					}
+					Ok(())
+				});
+			}
+		}
+		command
+	};
+
+	if policy.read_only_filesystem || policy.deny_tcp {
+		let data_dir = data_dir(uuid);
+		let tmp_dir = data_dir.join("tmp");
+		std::fs::create_dir_all(&tmp_dir)?;
+		command.env("OPENDECK_PLUGIN_DATA_DIR", &data_dir).env("TMPDIR", &tmp_dir);
+
+		let writable = if policy.read_only_filesystem {
+			Some([plugin_dir.to_path_buf(), data_dir, PathBuf::from("/dev/null"), PathBuf::from("/dev/shm")])
+		} else {
+			None
+		};
+		let ports = policy.deny_tcp.then(|| [*super::PORT_BASE, *super::PORT_BASE + 2]);
+		let ruleset = landlock::ruleset(writable.as_ref().map(|v| &v[..]), ports.as_ref().map(|v| &v[..]))?;
+
+		// SAFETY: `libc::prctl` and `libc::syscall` are async-signal-safe.
+		unsafe {
+			command.pre_exec(move || landlock::restrict_self(&ruleset));
+		}
+	}
+
+	Ok(command)
+}
+
+#[cfg(target_os = "linux")]
+static SYSTEMD_RUN_AVAILABLE: std::sync::LazyLock<bool> = std::sync::LazyLock::new(|| {
+	Command::new("systemd-run")
+		.args(["--user", "--scope", "--quiet", "--collect", "true"])
+		.stdout(std::process::Stdio::null())
+		.stderr(std::process::Stdio::null())
+		.status()
+		.is_ok_and(|status| status.success())
+});
+
+/// A minimal binding to the Landlock API, which allows unprivileged processes to restrict their own filesystem and network access.
+#[cfg(target_os = "linux")]
+mod landlock {
+	use std::io;
+	use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+	use std::os::unix::fs::OpenOptionsExt;
+	use std::path::PathBuf;
+
+	const CREATE_RULESET_VERSION: u32 = 1 << 0;
+	const RULE_PATH_BENEATH: u32 = 1;
+	const RULE_NET_PORT: u32 = 2;
+
+	const ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+	const ACCESS_FS_REMOVE_DIR: u64 = 1 << 4;
+	const ACCESS_FS_REMOVE_FILE: u64 = 1 << 5;
+	/// Creating character devices, directories, regular files, sockets, FIFOs, block devices and symbolic links.
+	const ACCESS_FS_MAKE: u64 = 0b111_1111 << 6;
+	const ACCESS_FS_REFER: u64 = 1 << 13;
+	const ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+	const ACCESS_NET_BIND_TCP: u64 = 1 << 0;
+	const ACCESS_NET_CONNECT_TCP: u64 = 1 << 1;
+
+	#[repr(C)]
+	struct RulesetAttr {
+		handled_access_fs: u64,
+		handled_access_net: u64,
+	}
+
+	#[repr(C, packed)]
+	struct PathBeneathAttr {
+		allowed_access: u64,
+		parent_fd: i32,
+	}
+
+	#[repr(C)]
+	struct NetPortAttr {
+		allowed_access: u64,
+		port: u64,
+	}
+
+	fn abi_version() -> i64 {
+		// SAFETY: querying the ABI version does not read any memory.
+		unsafe { libc::syscall(libc::SYS_landlock_create_ruleset, std::ptr::null::<RulesetAttr>(), 0usize, CREATE_RULESET_VERSION) }
+	}
+
+	fn add_rule<T>(ruleset: &OwnedFd, kind: u32, attr: &T) -> io::Result<()> {
+		// SAFETY: `attr` is a valid rule attribute of the type indicated by `kind`.
+		if unsafe { libc::syscall(libc::SYS_landlock_add_rule, ruleset.as_raw_fd(), kind, attr as *const T, 0u32) } != 0 {
+			return Err(io::Error::last_os_error());
+		}
+		Ok(())
+	}
+
+	/// Create a ruleset that denies writes outside of `writable` and TCP connections to ports other than `ports`, if they are provided.
+	pub fn ruleset(writable: Option<&[PathBuf]>, ports: Option<&[u16]>) -> Result<OwnedFd, anyhow::Error> {
+		let abi = abi_version();
+		if abi < 1 {
+			return Err(anyhow::anyhow!("filesystem and network restrictions require Landlock, which is not supported by this kernel"));
+		}
+		if ports.is_some() && abi < 4 {
+			return Err(anyhow::anyhow!("TCP restrictions require Landlock ABI version 4 (Linux 6.7) or newer"));
+		}
+
+		let mut handled_access_fs = 0;
+		if writable.is_some() {
+			handled_access_fs = ACCESS_FS_WRITE_FILE | ACCESS_FS_REMOVE_DIR | ACCESS_FS_REMOVE_FILE | ACCESS_FS_MAKE;
+			if abi >= 2 {
+				handled_access_fs |= ACCESS_FS_REFER;
+			}
+			if abi >= 3 {
+				handled_access_fs |= ACCESS_FS_TRUNCATE;
+			}
+		}
+		let handled_access_net = if ports.is_some() { ACCESS_NET_BIND_TCP | ACCESS_NET_CONNECT_TCP } else { 0 };
+
+		let attr = RulesetAttr {
+			handled_access_fs,
+			handled_access_net,
+		};
+		// Older kernels reject attributes larger than they support, so only pass the network field if it is used.
+		let size = if handled_access_net == 0 { size_of::<u64>() } else { size_of::<RulesetAttr>() };
+		// SAFETY: `attr` is valid for at least `size` bytes.
+		let fd = unsafe { libc::syscall(libc::SYS_landlock_create_ruleset, &attr as *const RulesetAttr, size, 0u32) };
+		if fd < 0 {
+			return Err(io::Error::last_os_error().into());
+		}
+		// SAFETY: the syscall returned a new file descriptor that nothing else owns.
+		let ruleset = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+
+		for path in writable.into_iter().flatten() {
+			let Ok(file) = std::fs::OpenOptions::new().read(true).custom_flags(libc::O_PATH | libc::O_CLOEXEC).open(path) else {
+				continue;
+			};
+			let allowed_access = if file.metadata()?.is_dir() {
+				handled_access_fs
+			} else {
+				handled_access_fs & (ACCESS_FS_WRITE_FILE | ACCESS_FS_TRUNCATE)
+			};
+			let rule = PathBeneathAttr {
+				allowed_access,
+				parent_fd: file.as_raw_fd(),
+			};
+			add_rule(&ruleset, RULE_PATH_BENEATH, &rule)?;
+		}
+
+		for port in ports.into_iter().flatten() {
+			let rule = NetPortAttr {
+				allowed_access: ACCESS_NET_CONNECT_TCP,
+				port: *port as u64,
+			};
+			add_rule(&ruleset, RULE_NET_PORT, &rule)?;
+		}
+
+		Ok(ruleset)
+	}
+
+	/// Enforce a ruleset on the calling process and all of its future children.
+	pub fn restrict_self(ruleset: &OwnedFd) -> io::Result<()> {
+		// SAFETY: both calls only take integer arguments.
+		unsafe {
+			if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+				return Err(io::Error::last_os_error());
+			}
+			if libc::syscall(libc::SYS_landlock_restrict_self, ruleset.as_raw_fd(), 0u32) != 0 {
+				return Err(io::Error::last_os_error());
+			}
+		}
+		Ok(())
+	}
+}
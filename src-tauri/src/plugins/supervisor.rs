@@ -0,0 +1,170 @@
+//! Supervision of plugin processes, restarting them with exponential backoff when they exit unexpectedly.
+
+use super::{INSTANCES, PluginInstance, SpawnRequest};
+
+use crate::APP_HANDLE;
+use crate::shared::{DEVICES, config_dir, log_dir};
+use crate::store::profiles::acquire_locks_mut;
+
+use std::process::ExitStatus;
+use std::sync::{LazyLock, mpsc};
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use serde::Serialize;
+use tauri::{Emitter, Manager};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const BASE_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// A plugin that runs for at least this long before exiting has its crash count reset.
+const STABLE_AFTER: Duration = Duration::from_secs(300);
+const LOG_TAIL_LINES: usize = 20;
+
+struct CrashRecord {
+	crashes: u32,
+	started: Instant,
+}
+
+static CRASHES: LazyLock<DashMap<String, CrashRecord>> = LazyLock::new(DashMap::new);
+
+#[derive(Clone, Serialize)]
+struct PluginCrashedEvent {
+	plugin: String,
+	code: Option<i32>,
+	status: String,
+	log_tail: String,
+	crashes: u32,
+	restarting: bool,
+}
+
+/// Record that a plugin process has just been spawned.
+pub fn note_started(uuid: &str) {
+	CRASHES
+		.entry(uuid.to_owned())
+		.and_modify(|record| record.started = Instant::now())
+		.or_insert_with(|| CrashRecord { crashes: 0, started: Instant::now() });
+}
+
+/// Forget the crash history of a plugin, for example after it has been manually reloaded.
+pub fn reset(uuid: &str) {
+	CRASHES.remove(uuid);
+}
+
+/// Start periodically checking plugin processes for unexpected exits.
+pub fn init_supervisor() {
+	tokio::spawn(async {
+		loop {
+			tokio::time::sleep(POLL_INTERVAL).await;
+			for (uuid, status) in reap_exited().await {
+				handle_exit(uuid, status).await;
+			}
+		}
+	});
+}
+
+/// Remove and return all plugin processes that have exited.
+async fn reap_exited() -> Vec<(String, ExitStatus)> {
+	let mut instances = INSTANCES.lock().await;
+	let exited = instances
+		.iter_mut()
+		.filter_map(|(uuid, instance)| match instance {
+			PluginInstance::Wine(child) | PluginInstance::Native(child) | PluginInstance::Node(child) | PluginInstance::Python(child) => match child.try_wait() {
+				Ok(Some(status)) => Some((uuid.clone(), status)),
+				_ => None,
+			},
+			PluginInstance::Webview => None,
+		})
+		.collect::<Vec<_>>();
+	for (uuid, _) in &exited {
+		instances.remove(uuid);
+	}
+	exited
+}
+
+/// Read the last few lines of a plugin's log file.
+fn log_tail(uuid: &str) -> String {
+	let Ok(contents) = std::fs::read(log_dir().join("plugins").join(format!("{uuid}.log"))) else {
+		return String::new();
+	};
+	let contents = String::from_utf8_lossy(&contents);
+	let lines = contents.lines().collect::<Vec<_>>();
+	lines[lines.len().saturating_sub(LOG_TAIL_LINES)..].join("\n")
+}
+
+async fn handle_exit(uuid: String, status: ExitStatus) {
+	let limit = crate::store::get_settings().value.plugin_restart_limit as u32;
+	let crashes = {
+		let mut record = CRASHES.entry(uuid.clone()).or_insert_with(|| CrashRecord { crashes: 0, started: Instant::now() });
+		if record.started.elapsed() >= STABLE_AFTER {
+			record.crashes = 0;
+		}
+		record.crashes += 1;
+		record.crashes
+	};
+	let restarting = crashes <= limit;
+
+	if restarting {
+		log::warn!("Plugin {uuid} exited unexpectedly ({status}); restarting (attempt {crashes} of {limit})");
+	} else {
+		log::error!("Plugin {uuid} exited unexpectedly ({status}); giving up after {} restarts", crashes - 1);
+	}
+
+	if let Some(window) = APP_HANDLE.get().unwrap().get_webview_window("main") {
+		let _ = window.emit(
+			"plugin_crashed",
+			PluginCrashedEvent {
+				plugin: uuid.clone(),
+				code: status.code(),
+				status: status.to_string(),
+				log_tail: log_tail(&uuid),
+				crashes,
+				restarting,
+			},
+		);
+	}
+
+	if !restarting {
+		return;
+	}
+
+	let delay = BASE_BACKOFF.saturating_mul(2_u32.saturating_pow(crashes - 1)).min(MAX_BACKOFF);
+	tokio::spawn(async move {
+		tokio::time::sleep(delay).await;
+		if let Err(error) = restart_plugin(&uuid).await {
+			log::error!("Failed to restart plugin {uuid}: {error:#}");
+		}
+	});
+}
+
+/// Reinitialise a plugin and resend `willAppear` for its instances on each device's selected profile.
+async fn restart_plugin(uuid: &str) -> Result<(), anyhow::Error> {
+	// The plugin may have been reloaded or removed by the user while we were waiting.
+	let path = config_dir().join("plugins").join(uuid);
+	if INSTANCES.lock().await.contains_key(uuid) || !path.exists() {
+		return Ok(());
+	}
+
+	let app = APP_HANDLE.get().unwrap();
+	let _ = super::deactivate_plugin(app, uuid).await;
+	let tx = (*app.state::<mpsc::Sender<SpawnRequest>>()).clone();
+	super::initialise_plugin(path, tx).await?;
+
+	let mut locks = acquire_locks_mut().await;
+	let devices = DEVICES.iter().map(|v| v.value().clone()).collect::<Vec<_>>();
+	for device in devices {
+		let Ok(selected_profile) = locks.device_stores.get_selected_profile(&device.id) else { continue };
+		let Ok(profile) = locks.profile_stores.get_page(&device, &selected_profile) else {
+			continue;
+		};
+		for slot in profile.keys.iter().chain(&profile.sliders).chain(&profile.infobars).flatten() {
+			for instance in std::iter::once(slot).chain(slot.children.iter().flatten()) {
+				if instance.action.plugin == uuid {
+					let _ = crate::events::outbound::will_appear::will_appear(instance).await;
+				}
+			}
+		}
+	}
+
+	Ok(())
+}
@@ -0,0 +1,312 @@
+//! Checks of plugin manifests that go beyond whether they can be deserialised, reporting problems with the JSON path they were found at.
+
+use super::manifest::{PluginManifest, read_manifest_value};
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{LazyLock, Mutex};
+use std::time::SystemTime;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+const CONTROLLERS: &[&str] = &["Keypad", "Encoder"];
+const PLATFORMS: &[&str] = &["windows", "mac", "linux"];
+const BUILTIN_LAYOUTS: &[&str] = &["$A0", "$A1", "$B1", "$B2", "$C1", "$X1"];
+
+/// The modification times of a plugin's manifest and of its platform-specific overrides.
+type ManifestTimes = [Option<SystemTime>; 2];
+
+/// The diagnostics of each plugin by ID, along with the modification times of the manifest files they were found in.
+static DIAGNOSTICS: LazyLock<Mutex<HashMap<String, (ManifestTimes, Vec<Diagnostic>)>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+	Warning,
+	Error,
+}
+
+#[derive(Clone, Serialize)]
+pub struct Diagnostic {
+	pub severity: Severity,
+	/// The JSON path of the manifest value that the diagnostic refers to, such as `$.Actions[0].UUID`.
+	pub path: String,
+	pub message: String,
+}
+
+struct Validator<'a> {
+	base_path: &'a Path,
+	diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator<'_> {
+	fn report(&mut self, severity: Severity, path: impl Into<String>, message: impl Into<String>) {
+		self.diagnostics.push(Diagnostic {
+			severity,
+			path: path.into(),
+			message: message.into(),
+		});
+	}
+
+	/// Check that a file referenced by the manifest exists, trying the extensions that the manifest may omit for images.
+	fn check_file(&mut self, path: &str, relative: &str, image: bool) {
+		let full = self.base_path.join(relative);
+		let exists = full.is_file() || (image && [".svg", "@2x.png", ".png"].iter().any(|suffix| self.base_path.join(format!("{relative}{suffix}")).is_file()));
+		if !exists {
+			self.report(Severity::Warning, path, format!("file \"{relative}\" not found"));
+		}
+	}
+
+	fn check_image(&mut self, object: &Map<String, Value>, path: &str, keys: &[&str]) {
+		if let Some((key, value)) = get(object, keys) {
+			match value.as_str() {
+				Some("") | Some("actionDefaultImage") => {}
+				Some(image) => self.check_file(&format!("{path}.{key}"), image, true),
+				None => self.report(Severity::Error, format!("{path}.{key}"), "expected a string"),
+			}
+		}
+	}
+}
+
+/// Look up the first of several names that a manifest field may be written as.
+fn get<'a>(object: &'a Map<String, Value>, keys: &[&'a str]) -> Option<(&'a str, &'a Value)> {
+	keys.iter().find_map(|key| object.get(*key).map(|value| (*key, value)))
+}
+
+fn manifest_times(base_path: &Path) -> ManifestTimes {
+	let modified = |name: &str| std::fs::metadata(base_path.join(name)).and_then(|v| v.modified()).ok();
+	[modified("manifest.json"), modified(&format!("manifest.{}.json", std::env::consts::OS))]
+}
+
+/// Get the diagnostics of a plugin, only validating its manifest again if it has been modified since the diagnostics were last found.
+pub fn cached_diagnostics(id: &str, base_path: &Path) -> Vec<Diagnostic> {
+	let times = manifest_times(base_path);
+	if let Some((cached, diagnostics)) = DIAGNOSTICS.lock().unwrap().get(id)
+		&& *cached == times
+	{
+		return diagnostics.clone();
+	}
+	let diagnostics = validate_plugin(base_path);
+	DIAGNOSTICS.lock().unwrap().insert(id.to_owned(), (times, diagnostics.clone()));
+	diagnostics
+}
+
+/// Discard the cached diagnostics of a plugin, such as when it is installed or reloaded and files referenced by its manifest may have changed.
+pub fn invalidate_diagnostics(id: &str) {
+	DIAGNOSTICS.lock().unwrap().remove(id);
+}
+
+/// Validate the manifest of the plugin in a directory, returning any problems found.
+pub fn validate_plugin(base_path: &Path) -> Vec<Diagnostic> {
+	let mut validator = Validator { base_path, diagnostics: vec![] };
+	let manifest = match read_manifest_value(base_path) {
+		Ok(manifest) => manifest,
+		Err(error) => {
+			validator.report(Severity::Error, "$", format!("{error:#}"));
+			return validator.diagnostics;
+		}
+	};
+	let Some(root) = manifest.as_object() else {
+		validator.report(Severity::Error, "$", "manifest is not a JSON object");
+		return validator.diagnostics;
+	};
+
+	for keys in [["Name", "name"], ["Author", "author"], ["Version", "version"]] {
+		if get(root, &keys).is_none() {
+			validator.report(Severity::Error, format!("$.{}", keys[0]), "required field is missing");
+		}
+	}
+	match get(root, &["Icon", "icon"]) {
+		Some(_) => validator.check_image(root, "$", &["Icon", "icon"]),
+		None => validator.report(Severity::Error, "$.Icon", "required field is missing"),
+	}
+	validator.check_image(root, "$", &["CategoryIcon", "category_icon"]);
+	if let Some((key, Value::String(path))) = get(root, &["PropertyInspectorPath", "property_inspector_path"]) {
+		validator.check_file(&format!("$.{key}"), path, false);
+	}
+
+	let code_path_keys = [
+		["CodePath", "code_path"],
+		["CodePathWin", "code_path_windows"],
+		["CodePathMac", "code_path_macos"],
+		["CodePathLin", "code_path_linux"],
+	];
+	let mut has_code_path = false;
+	for keys in code_path_keys {
+		if let Some((key, value)) = get(root, &keys) {
+			has_code_path = true;
+			if let Some(path) = value.as_str() {
+				validator.check_file(&format!("$.{key}"), path, false);
+			}
+		}
+	}
+	if let Some((key, Value::Object(paths))) = get(root, &["CodePaths", "code_paths"]) {
+		has_code_path |= !paths.is_empty();
+		for (target, path) in paths {
+			if let Some(path) = path.as_str() {
+				validator.check_file(&format!("$.{key}.{target}"), path, false);
+			}
+		}
+	}
+	if !has_code_path {
+		validator.report(Severity::Error, "$.CodePath", "no code path is specified");
+	}
+
+	match get(root, &["OS", "os"]) {
+		Some((key, Value::Array(platforms))) => {
+			if platforms.is_empty() {
+				validator.report(Severity::Error, format!("$.{key}"), "no supported platforms are listed");
+			}
+			for (index, platform) in platforms.iter().enumerate() {
+				let name = platform.as_object().and_then(|v| get(v, &["Platform", "platform"])).and_then(|(_, v)| v.as_str());
+				if !name.is_some_and(|name| PLATFORMS.contains(&name)) {
+					validator.report(
+						Severity::Warning,
+						format!("$.{key}[{index}].Platform"),
+						format!("unknown platform, expected one of {}", PLATFORMS.join(", ")),
+					);
+				}
+			}
+		}
+		Some((key, _)) => validator.report(Severity::Error, format!("$.{key}"), "expected an array"),
+		None => validator.report(Severity::Error, "$.OS", "required field is missing"),
+	}
+
+	match get(root, &["Actions", "actions"]) {
+		Some((key, Value::Array(actions))) => {
+			let plugin_uuid = base_path.file_name().map(|v| v.to_string_lossy().trim_end_matches(".sdPlugin").to_owned()).unwrap_or_default();
+			let mut seen = HashMap::new();
+			for (index, action) in actions.iter().enumerate() {
+				let path = format!("$.{key}[{index}]");
+				match action.as_object() {
+					Some(action) => validate_action(&mut validator, action, &path, &plugin_uuid, &mut seen),
+					None => validator.report(Severity::Error, path, "expected an object"),
+				}
+			}
+		}
+		Some((key, _)) => validator.report(Severity::Error, format!("$.{key}"), "expected an array"),
+		None => validator.report(Severity::Error, "$.Actions", "required field is missing"),
+	}
+
+	// Any remaining problem that prevents the plugin from loading at all is reported as serde describes it.
+	if let Err(error) = serde_json::from_value::<PluginManifest>(manifest) {
+		validator.report(Severity::Error, "$", format!("failed to parse manifest: {error}"));
+	}
+
+	validator.diagnostics
+}
+
+fn validate_action(validator: &mut Validator, action: &Map<String, Value>, path: &str, plugin_uuid: &str, seen: &mut HashMap<String, String>) {
+	match get(action, &["UUID", "uuid"]) {
+		Some((key, Value::String(uuid))) => {
+			let uuid_path = format!("{path}.{key}");
+			if let Some(previous) = seen.get(uuid) {
+				validator.report(Severity::Error, &uuid_path, format!("duplicate action UUID, also used by {previous}"));
+			} else {
+				seen.insert(uuid.clone(), path.to_owned());
+			}
+			if !plugin_uuid.is_empty() && !uuid.starts_with(plugin_uuid) {
+				validator.report(Severity::Warning, uuid_path, format!("action UUID should be prefixed with the plugin UUID \"{plugin_uuid}\""));
+			}
+		}
+		Some((key, _)) => validator.report(Severity::Error, format!("{path}.{key}"), "expected a string"),
+		None => validator.report(Severity::Error, format!("{path}.UUID"), "required field is missing"),
+	}
+	if get(action, &["Name", "name"]).is_none() {
+		validator.report(Severity::Error, format!("{path}.Name"), "required field is missing");
+	}
+	validator.check_image(action, path, &["Icon", "icon"]);
+	if let Some((key, Value::String(inspector))) = get(action, &["PropertyInspectorPath", "property_inspector"])
+		&& !inspector.is_empty()
+	{
+		validator.check_file(&format!("{path}.{key}"), inspector, false);
+	}
+
+	match get(action, &["States", "states"]) {
+		Some((key, Value::Array(states))) => {
+			if states.is_empty() {
+				validator.report(Severity::Error, format!("{path}.{key}"), "at least one state is required");
+			} else if states.len() > 2 {
+				validator.report(
+					Severity::Warning,
+					format!("{path}.{key}"),
+					"actions with more than two states are not supported by other Stream Deck software",
+				);
+			}
+			for (index, state) in states.iter().enumerate() {
+				if let Some(state) = state.as_object() {
+					validator.check_image(state, &format!("{path}.{key}[{index}]"), &["Image", "image"]);
+				}
+			}
+		}
+		Some((key, _)) => validator.report(Severity::Error, format!("{path}.{key}"), "expected an array"),
+		None => validator.report(Severity::Error, format!("{path}.States"), "required field is missing"),
+	}
+
+	let mut controllers = vec!["Keypad"];
+	if let Some((key, value)) = get(action, &["Controllers", "controllers"]) {
+		controllers.clear();
+		match value.as_array() {
+			Some(values) if values.is_empty() => validator.report(Severity::Error, format!("{path}.{key}"), "at least one controller is required"),
+			Some(values) => {
+				for (index, controller) in values.iter().enumerate() {
+					match controller.as_str() {
+						Some(controller) if CONTROLLERS.contains(&controller) => controllers.push(controller),
+						_ => validator.report(
+							Severity::Error,
+							format!("{path}.{key}[{index}]"),
+							format!("unknown controller {controller}, expected one of {}", CONTROLLERS.join(", ")),
+						),
+					}
+				}
+			}
+			None => validator.report(Severity::Error, format!("{path}.{key}"), "expected an array"),
+		}
+	}
+
+	let encoder = get(action, &["Encoder", "encoder"]);
+	match (controllers.contains(&"Encoder"), encoder) {
+		(true, None) => validator.report(
+			Severity::Warning,
+			format!("{path}.Encoder"),
+			"encoder action has no Encoder block, so the default layout and icon are used",
+		),
+		(false, Some((key, _))) => validator.report(Severity::Warning, format!("{path}.{key}"), "Encoder block is ignored because Controllers does not include Encoder"),
+		(_, Some((key, Value::Object(encoder)))) => validate_encoder(validator, encoder, &format!("{path}.{key}")),
+		(_, Some((key, _))) => validator.report(Severity::Error, format!("{path}.{key}"), "expected an object"),
+		(false, None) => {}
+	}
+}
+
+fn validate_encoder(validator: &mut Validator, encoder: &Map<String, Value>, path: &str) {
+	validator.check_image(encoder, path, &["Icon", "icon"]);
+	validator.check_image(encoder, path, &["background"]);
+
+	let Some((key, layout)) = get(encoder, &["layout"]) else { return };
+	let layout_path = format!("{path}.{key}");
+	let Some(layout) = layout.as_str() else {
+		validator.report(Severity::Error, layout_path, "expected a string");
+		return;
+	};
+	if layout.is_empty() {
+		return;
+	}
+	if layout.starts_with('$') {
+		if !BUILTIN_LAYOUTS.contains(&layout) {
+			validator.report(Severity::Error, layout_path, format!("unknown built-in layout, expected one of {}", BUILTIN_LAYOUTS.join(", ")));
+		}
+		return;
+	}
+
+	let file = validator.base_path.join(layout);
+	match (file.canonicalize(), validator.base_path.canonicalize()) {
+		(Ok(resolved), Ok(base)) if !resolved.starts_with(&base) => validator.report(Severity::Error, layout_path, "layout file is outside of the plugin directory"),
+		(Ok(resolved), _) => match std::fs::read(&resolved).map(|v| serde_json::from_slice::<Value>(&v)) {
+			Ok(Ok(_)) => {}
+			Ok(Err(error)) => validator.report(Severity::Error, layout_path, format!("layout file is not valid JSON: {error}")),
+			Err(error) => validator.report(Severity::Error, layout_path, format!("failed to read layout file: {error}")),
+		},
+		(Err(_), _) => validator.report(Severity::Error, layout_path, format!("layout file \"{layout}\" not found")),
+	}
+}
@@ -18,7 +18,7 @@ fn mime(extension: &str) -> String {
 pub async fn init_webserver(prefix: PathBuf) {
 	let prefix = prefix.canonicalize().unwrap();
 	let server = {
-		let listener = std::net::TcpListener::bind(format!("0.0.0.0:{}", *super::PORT_BASE + 2)).unwrap();
+		let listener = std::net::TcpListener::bind(format!("{}:{}", super::bind_address(), *super::PORT_BASE + 2)).unwrap();
 
 		#[cfg(windows)]
 		{
@@ -0,0 +1,154 @@
+//! Rules that select profiles during certain times of day and days of the week.
+
+use crate::store::{NotProfile, Store};
+
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use anyhow::bail;
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleRule {
+	/// The days of the week that the rule applies on, numbered from 0 for Monday, where an empty list means every day.
+	#[serde(default)]
+	pub days: Vec<u8>,
+	/// The time of day that the rule starts applying, in the format `HH:MM`.
+	pub start: String,
+	/// The time of day that the rule stops applying, which continues into the next day if it is not after the start time.
+	pub end: String,
+	/// The profile to select on each device while the rule applies.
+	pub profiles: HashMap<String, String>,
+}
+
+pub type ScheduleRules = Vec<ScheduleRule>;
+impl NotProfile for ScheduleRules {}
+
+pub static SCHEDULES: LazyLock<RwLock<Store<ScheduleRules>>> = LazyLock::new(|| RwLock::new(Store::new("schedules", &crate::shared::config_dir(), vec![]).unwrap()));
+
+/// A source of the current local time, which can be replaced to check rules against other times.
+pub trait Clock: Send + Sync {
+	fn now(&self) -> NaiveDateTime;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+	fn now(&self) -> NaiveDateTime {
+		Local::now().naive_local()
+	}
+}
+
+impl ScheduleRule {
+	fn applies_on(&self, date: NaiveDate) -> bool {
+		self.days.is_empty() || self.days.contains(&(date.weekday().num_days_from_monday() as u8))
+	}
+
+	/// Check that the times of the rule are in the format `HH:MM` and that its days are numbered from 0 to 6.
+	pub fn validate(&self) -> Result<(), anyhow::Error> {
+		for time in [&self.start, &self.end] {
+			if time.len() != 5 || NaiveTime::parse_from_str(time, "%H:%M").is_err() {
+				bail!("invalid time \"{time}\", expected HH:MM");
+			}
+		}
+		if let Some(day) = self.days.iter().find(|day| **day > 6) {
+			bail!("invalid day {day}, expected a number from 0 to 6");
+		}
+		Ok(())
+	}
+
+	/// Check whether the rule applies at a time, where the days of a rule that continues past midnight refer to the day that it starts on.
+	pub fn is_active(&self, now: NaiveDateTime) -> bool {
+		let (Ok(start), Ok(end)) = (NaiveTime::parse_from_str(&self.start, "%H:%M"), NaiveTime::parse_from_str(&self.end, "%H:%M")) else {
+			return false;
+		};
+		let time = now.time();
+		if start < end {
+			start <= time && time < end && self.applies_on(now.date())
+		} else if time >= start {
+			self.applies_on(now.date())
+		} else {
+			time < end && now.date().pred_opt().is_some_and(|date| self.applies_on(date))
+		}
+	}
+}
+
+/// Get the indices of the rules that apply at a time.
+pub fn active_rules(rules: &[ScheduleRule], now: NaiveDateTime) -> Vec<usize> {
+	rules.iter().enumerate().filter(|(_, rule)| rule.is_active(now)).map(|(index, _)| index).collect()
+}
+
+/// Get the profile that the first of the active rules with a profile for a device selects.
+pub fn scheduled_profile<'a>(rules: &'a [ScheduleRule], active: &[usize], device: &str) -> Option<&'a String> {
+	active.iter().find_map(|index| rules.get(*index)?.profiles.get(device))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn rule(days: &[u8], start: &str, end: &str) -> ScheduleRule {
+		ScheduleRule {
+			days: days.to_vec(),
+			start: start.to_owned(),
+			end: end.to_owned(),
+			profiles: HashMap::new(),
+		}
+	}
+
+	/// A time on the week beginning Monday 2024-01-01, where `day` is numbered from 0 for Monday.
+	fn at(day: u32, time: &str) -> NaiveDateTime {
+		NaiveDate::from_ymd_opt(2024, 1, 1 + day).unwrap().and_time(NaiveTime::parse_from_str(time, "%H:%M").unwrap())
+	}
+
+	#[test]
+	fn applies_between_start_and_end() {
+		let rule = rule(&[], "09:00", "17:00");
+		assert!(!rule.is_active(at(0, "08:59")));
+		assert!(rule.is_active(at(0, "09:00")));
+		assert!(rule.is_active(at(0, "16:59")));
+		assert!(!rule.is_active(at(0, "17:00")));
+	}
+
+	#[test]
+	fn wraps_around_midnight() {
+		let rule = rule(&[], "22:00", "06:00");
+		assert!(rule.is_active(at(0, "22:00")));
+		assert!(rule.is_active(at(0, "23:59")));
+		assert!(rule.is_active(at(1, "00:00")));
+		assert!(rule.is_active(at(1, "05:59")));
+		assert!(!rule.is_active(at(1, "06:00")));
+		assert!(!rule.is_active(at(1, "21:59")));
+	}
+
+	#[test]
+	fn applies_all_day_when_start_equals_end() {
+		let rule = rule(&[2], "08:00", "08:00");
+		assert!(rule.is_active(at(2, "08:00")));
+		assert!(rule.is_active(at(2, "23:59")));
+		assert!(rule.is_active(at(3, "07:59")));
+		assert!(!rule.is_active(at(3, "08:00")));
+		assert!(!rule.is_active(at(2, "07:59")));
+	}
+
+	#[test]
+	fn uses_start_day_after_midnight() {
+		// A rule for Friday nights applies in the early hours of Saturday, but not those of Friday.
+		let rule = rule(&[4], "22:00", "02:00");
+		assert!(rule.is_active(at(4, "23:00")));
+		assert!(rule.is_active(at(5, "01:00")));
+		assert!(!rule.is_active(at(4, "01:00")));
+		assert!(!rule.is_active(at(5, "23:00")));
+	}
+
+	#[test]
+	fn validates_times_and_days() {
+		assert!(rule(&[0, 6], "00:00", "23:59").validate().is_ok());
+		assert!(rule(&[], "9:00", "17:00").validate().is_err());
+		assert!(rule(&[], "09:00", "24:00").validate().is_err());
+		assert!(rule(&[], "09:00", "noon").validate().is_err());
+		assert!(rule(&[7], "09:00", "17:00").validate().is_err());
+	}
+}
@@ -430,6 +430,9 @@ pub struct ActionInstance {
 	pub current_state: u16,
 	pub settings: serde_json::Value,
 	pub children: Option<Vec<ActionInstance>>,
+	/// The page of actions held by a folder.
+	#[serde(default)]
+	pub page: Option<Box<Profile>>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -444,6 +447,98 @@ pub struct Profile {
 	pub stale: bool,
 }
 
+/// The separator between the ID of a profile and the positions of the folders leading to one of its pages.
+pub const PAGE_SEPARATOR: char = '~';
+
+/// Get the ID of the profile that contains a page.
+pub fn page_root(id: &str) -> &str {
+	id.split(PAGE_SEPARATOR).next().unwrap_or(id)
+}
+
+/// Get the ID of the page containing the folder that holds a page, if it is not a profile itself.
+pub fn page_parent(id: &str) -> Option<&str> {
+	id.rsplit_once(PAGE_SEPARATOR).map(|(parent, _)| parent)
+}
+
+/// Get the path of the image directory of a page relative to that of its device, nesting folder pages within their profile.
+pub fn page_images_path(id: &str) -> String {
+	id.replace(PAGE_SEPARATOR, &format!("/{PAGE_SEPARATOR}"))
+}
+
+impl Profile {
+	/// Create an empty page for a folder, with a back key in the first position.
+	pub fn new_page(device: &DeviceInfo, id: String, back: Action) -> Self {
+		let mut keys = vec![None; (device.rows * device.columns + device.touchpoints) as usize];
+		if let Some(first) = keys.first_mut() {
+			*first = Some(ActionInstance {
+				states: back.states.clone(),
+				action: back,
+				context: ActionContext {
+					device: device.id.clone(),
+					profile: id.clone(),
+					controller: "Keypad".to_owned(),
+					position: 0,
+					index: 0,
+				},
+				current_state: 0,
+				settings: serde_json::Value::Object(serde_json::Map::new()),
+				children: None,
+				page: None,
+			});
+		}
+
+		Self {
+			id,
+			keys,
+			sliders: vec![None; device.encoders as usize],
+			infobars: vec![None; device.infobars as usize],
+			stale: false,
+		}
+	}
+
+	/// Get a page of this profile, which is either the profile itself or a folder page nested within it.
+	pub fn page(&self, id: &str) -> Option<&Profile> {
+		let mut page = self;
+		for position in id.split(PAGE_SEPARATOR).skip(1) {
+			page = page.keys.get(position.parse::<usize>().ok()?)?.as_ref()?.page.as_deref()?;
+		}
+		Some(page)
+	}
+
+	pub fn page_mut(&mut self, id: &str) -> Option<&mut Profile> {
+		let mut page = self;
+		for position in id.split(PAGE_SEPARATOR).skip(1) {
+			page = page.keys.get_mut(position.parse::<usize>().ok()?)?.as_mut()?.page.as_deref_mut()?;
+		}
+		Some(page)
+	}
+
+	/// Change the ID of this page, updating the contexts of the instances on it and on the pages nested within it.
+	pub fn set_id(&mut self, id: String) {
+		for instance in self.keys.iter_mut().chain(&mut self.sliders).chain(&mut self.infobars).flatten() {
+			instance.context.profile = id.clone();
+			for child in instance.children.iter_mut().flatten() {
+				child.context.profile = id.clone();
+			}
+			if let Some(page) = &mut instance.page {
+				page.set_id(format!("{id}{PAGE_SEPARATOR}{}", instance.context.position));
+			}
+		}
+		self.id = id;
+	}
+
+	/// Iterate over every instance on this page and the pages nested within it, including the children of multi-actions.
+	pub fn all_instances(&self) -> Box<dyn Iterator<Item = &ActionInstance> + '_> {
+		Box::new(self.keys.iter().chain(&self.sliders).chain(&self.infobars).flatten().flat_map(|instance| {
+			let nested: Box<dyn Iterator<Item = &ActionInstance> + '_> = match &instance.page {
+				Some(page) => page.all_instances(),
+				None => Box::new(std::iter::empty()),
+			};
+			std::iter::once(instance).chain(instance.children.iter().flatten()).chain(nested)
+		}))
+	}
+}
+
 /// A map of category names to a list of actions in that category.
 pub static CATEGORIES: LazyLock<RwLock<HashMap<String, Category>>> = LazyLock::new(|| {
 	let mut hashmap = HashMap::new();
@@ -478,6 +573,46 @@ pub static CATEGORIES: LazyLock<RwLock<HashMap<String, Category>>> = LazyLock::n
 					}
 				))
 				.unwrap(),
+				serde_json::from_value(serde_json::json!(
+					{
+						"name": "Gesture Action",
+						"icon": "opendeck/gesture-action.png",
+						"plugin": "opendeck",
+						"uuid": "opendeck.gestureaction",
+						"tooltip": "Run different actions on a press, long press, double tap or hold",
+						"controllers": [ "Keypad" ],
+						"states": [ { "image": "opendeck/gesture-action.png" } ],
+						"supported_in_multi_actions": false
+					}
+				))
+				.unwrap(),
+				serde_json::from_value(serde_json::json!(
+					{
+						"name": "Folder",
+						"icon": "opendeck/folder.png",
+						"plugin": "opendeck",
+						"uuid": "opendeck.folder",
+						"tooltip": "Open a page of additional actions",
+						"controllers": [ "Keypad" ],
+						"states": [ { "image": "opendeck/folder.png" } ],
+						"supported_in_multi_actions": false
+					}
+				))
+				.unwrap(),
+				serde_json::from_value(serde_json::json!(
+					{
+						"name": "Back",
+						"icon": "opendeck/folder-back.png",
+						"plugin": "opendeck",
+						"uuid": "opendeck.folderback",
+						"tooltip": "Return to the page containing this folder",
+						"controllers": [ "Keypad" ],
+						"states": [ { "image": "opendeck/folder-back.png" } ],
+						"visible_in_action_list": false,
+						"supported_in_multi_actions": false
+					}
+				))
+				.unwrap(),
 			],
 		},
 	);
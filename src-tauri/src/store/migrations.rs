@@ -0,0 +1,96 @@
+//! Versioning of stored JSON, so that files written in an older format can be upgraded when they are loaded.
+
+use serde::de::Error;
+use serde_json::{Map, Value, json};
+
+pub const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+/// A function that upgrades stored JSON from one schema version to the next.
+pub type Migration = fn(&mut Value) -> Result<(), serde_json::Error>;
+
+/// Migrations for profiles, where the migration at index `i` upgrades a profile from version `i` to version `i + 1`.
+pub const PROFILE_MIGRATIONS: &[Migration] = &[profile_v0_to_v1];
+
+/// Migrations for application profile rules, indexed in the same way as for profiles.
+pub const APPLICATION_PROFILES_MIGRATIONS: &[Migration] = &[application_profiles_v0_to_v1];
+
+/// An error for stored JSON written by a newer version of the application, which must be left as it is rather than treated as corrupt.
+#[derive(Debug)]
+pub struct NewerSchemaVersion {
+	pub version: usize,
+	pub latest: usize,
+}
+
+impl std::fmt::Display for NewerSchemaVersion {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "schema version {} is newer than the latest supported version {}", self.version, self.latest)
+	}
+}
+
+impl std::error::Error for NewerSchemaVersion {}
+
+/// Check that stored JSON was not written with a newer schema version than the given migrations can upgrade from.
+pub fn check_version(value: &Value, migrations: &[Migration]) -> Result<(), NewerSchemaVersion> {
+	match value.get(SCHEMA_VERSION_KEY).and_then(Value::as_u64) {
+		Some(version) if version as usize > migrations.len() => Err(NewerSchemaVersion {
+			version: version as usize,
+			latest: migrations.len(),
+		}),
+		_ => Ok(()),
+	}
+}
+
+/// Record the schema version, which is the number of migrations that exist for the type, in stored JSON.
+pub fn stamp(value: &mut Value, migrations: &[Migration]) {
+	if let Some(map) = value.as_object_mut() {
+		map.insert(SCHEMA_VERSION_KEY.to_owned(), migrations.len().into());
+	}
+}
+
+/// Remove the schema version from stored JSON and apply each migration newer than it in order.
+pub fn migrate(value: &mut Value, migrations: &[Migration]) -> Result<(), serde_json::Error> {
+	let version = match value.as_object_mut().and_then(|map| map.remove(SCHEMA_VERSION_KEY)) {
+		None => 0,
+		Some(version) => version.as_u64().ok_or_else(|| serde_json::Error::custom(format!("invalid schema version {version}")))? as usize,
+	};
+
+	if version > migrations.len() {
+		return Err(serde_json::Error::custom(format!(
+			"schema version {version} is newer than the latest supported version {}",
+			migrations.len()
+		)));
+	}
+
+	for (from, migration) in migrations.iter().enumerate().skip(version) {
+		migration(value).map_err(|error| serde_json::Error::custom(format!("failed to migrate from schema version {from}: {error}")))?;
+	}
+
+	Ok(())
+}
+
+/// Profiles written before infobars were supported have no `infobars` array.
+fn profile_v0_to_v1(value: &mut Value) -> Result<(), serde_json::Error> {
+	let map = value.as_object_mut().ok_or_else(|| serde_json::Error::custom("profile is not an object"))?;
+	map.entry("infobars").or_insert_with(|| Value::Array(vec![]));
+	Ok(())
+}
+
+/// Application profiles were a map from exact application names, or `opendeck_default`, to the profile for each device, and are now an ordered list of rules.
+fn application_profiles_v0_to_v1(value: &mut Value) -> Result<(), serde_json::Error> {
+	let map = value.as_object_mut().ok_or_else(|| serde_json::Error::custom("application profiles are not an object"))?;
+	let default = map.remove("opendeck_default").unwrap_or_else(|| Value::Object(Map::new()));
+	let rules = map
+		.iter()
+		.map(|(name, profiles)| {
+			json!({
+				"field": "app_name",
+				"mode": "exact",
+				"pattern": name,
+				"case_sensitive": true,
+				"profiles": profiles,
+			})
+		})
+		.collect::<Vec<_>>();
+	*value = json!({ "rules": rules, "default": default });
+	Ok(())
+}
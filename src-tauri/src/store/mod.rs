@@ -1,5 +1,7 @@
+pub mod migrations;
 pub mod profiles;
 mod simplified_profile;
+pub mod streamdeck_profile;
 
 use crate::shared::is_flatpak;
 
@@ -13,21 +15,32 @@ pub trait FromAndIntoDiskValue
 where
 	Self: Sized,
 {
+	/// Migrations to apply to stored values written with an older schema version, in order.
+	const MIGRATIONS: &'static [migrations::Migration];
+
 	#[allow(clippy::wrong_self_convention)]
 	fn into_value(&self) -> Result<serde_json::Value, serde_json::Error>;
 	fn from_value(_: serde_json::Value, _: &Path) -> Result<Self, serde_json::Error>;
 }
 
-pub trait NotProfile {}
+pub trait NotProfile {
+	/// Migrations to apply to stored values written with an older schema version, in order.
+	const MIGRATIONS: &'static [migrations::Migration] = &[];
+}
 
 impl<T> FromAndIntoDiskValue for T
 where
 	T: Serialize + for<'a> Deserialize<'a> + NotProfile,
 {
+	const MIGRATIONS: &'static [migrations::Migration] = <T as NotProfile>::MIGRATIONS;
+
 	fn into_value(&self) -> Result<serde_json::Value, serde_json::Error> {
-		serde_json::to_value(self)
+		let mut value = serde_json::to_value(self)?;
+		migrations::stamp(&mut value, <T as NotProfile>::MIGRATIONS);
+		Ok(value)
 	}
-	fn from_value(value: serde_json::Value, _: &Path) -> Result<T, serde_json::Error> {
+	fn from_value(mut value: serde_json::Value, _: &Path) -> Result<T, serde_json::Error> {
+		migrations::migrate(&mut value, <T as NotProfile>::MIGRATIONS)?;
 		serde_json::from_value(value)
 	}
 }
@@ -39,6 +52,8 @@ where
 {
 	pub value: T,
 	path: PathBuf,
+	/// Whether the file was written by a newer version of the application, in which case it is never overwritten.
+	read_only: bool,
 }
 
 impl<T> Store<T>
@@ -48,29 +63,73 @@ where
 	/// Validate that a file contains valid data for type T
 	fn validate_file_contents(path: &Path) -> Result<T, anyhow::Error> {
 		let file_contents = fs::read(path)?;
-		let value: T = T::from_value(serde_json::from_slice(&file_contents)?, path)?;
+		let value = serde_json::from_slice(&file_contents)?;
+		migrations::check_version(&value, T::MIGRATIONS)?;
+		let value: T = T::from_value(value, path)?;
 		Ok(value)
 	}
 
+	/// Validate the contents of the main file of a store, moving it aside if it exists but cannot be parsed so that it is not overwritten
+	fn validate_or_quarantine(path: &Path) -> Result<T, anyhow::Error> {
+		let result = Self::validate_file_contents(path);
+		if let Err(error) = &result
+			&& error.downcast_ref::<std::io::Error>().is_none()
+			&& error.downcast_ref::<migrations::NewerSchemaVersion>().is_none()
+		{
+			let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs();
+			let quarantine_path = path.with_extension(format!("json.corrupt-{timestamp}"));
+			match fs::rename(path, &quarantine_path) {
+				Ok(()) => log::error!("Failed to parse {}, so it was moved to {}: {error:#}", path.display(), quarantine_path.display()),
+				Err(rename_error) => log::error!("Failed to parse {} or move it aside: {error:#}; {rename_error}", path.display()),
+			}
+		}
+		result
+	}
+
 	/// Create a new Store given an ID and storage directory
 	pub fn new(id: &str, config_dir: &Path, default: T) -> Result<Self, anyhow::Error> {
 		let path = config_dir.join(format!("{}.json", id));
 		let temp_path = path.with_extension("json.temp");
 		let backup_path = path.with_extension("json.bak");
 
-		if let Ok(value) = Self::validate_file_contents(&path) {
-			let _ = fs::remove_file(&temp_path);
-			let _ = fs::remove_file(&backup_path);
-			Ok(Self { path, value })
-		} else if let Ok(value) = Self::validate_file_contents(&temp_path) {
+		match Self::validate_or_quarantine(&path) {
+			Ok(value) => {
+				let _ = fs::remove_file(&temp_path);
+				let _ = fs::remove_file(&backup_path);
+				return Ok(Self { path, value, read_only: false });
+			}
+			Err(error) if Self::is_newer(&path, &error) => {
+				return Ok(Self {
+					path,
+					value: default,
+					read_only: true,
+				});
+			}
+			Err(_) => (),
+		}
+
+		if let Ok(value) = Self::validate_file_contents(&temp_path) {
 			fs::rename(&temp_path, &path)?;
-			Ok(Self { path, value })
+			Ok(Self { path, value, read_only: false })
 		} else if let Ok(value) = Self::validate_file_contents(&backup_path) {
 			fs::rename(&backup_path, &path)?;
-			Ok(Self { path, value })
+			Ok(Self { path, value, read_only: false })
 		} else {
-			Ok(Self { path, value: default })
+			Ok(Self {
+				path,
+				value: default,
+				read_only: false,
+			})
+		}
+	}
+
+	/// Check whether a file could not be loaded because it was written by a newer version, in which case it is used read-only with the default value.
+	fn is_newer(path: &Path, error: &anyhow::Error) -> bool {
+		if error.downcast_ref::<migrations::NewerSchemaVersion>().is_none() {
+			return false;
 		}
+		log::error!("{} was written by a newer version, so defaults are used and it will not be overwritten: {error:#}", path.display());
+		true
 	}
 
 	/// Create a new Store given an ID and storage directory, without removing the temporary and backup files that may be in use by another instance of Store
@@ -79,19 +138,37 @@ where
 		let temp_path = path.with_extension("json.temp");
 		let backup_path = path.with_extension("json.bak");
 
-		if let Ok(value) = Self::validate_file_contents(&path) {
-			Self { path, value }
-		} else if let Ok(value) = Self::validate_file_contents(&temp_path) {
-			Self { path, value }
+		match Self::validate_or_quarantine(&path) {
+			Ok(value) => return Self { path, value, read_only: false },
+			Err(error) if Self::is_newer(&path, &error) => {
+				return Self {
+					path,
+					value: default,
+					read_only: true,
+				};
+			}
+			Err(_) => (),
+		}
+
+		if let Ok(value) = Self::validate_file_contents(&temp_path) {
+			Self { path, value, read_only: false }
 		} else if let Ok(value) = Self::validate_file_contents(&backup_path) {
-			Self { path, value }
+			Self { path, value, read_only: false }
 		} else {
-			Self { path, value: default }
+			Self {
+				path,
+				value: default,
+				read_only: false,
+			}
 		}
 	}
 
 	/// Save the relevant Store as a file
 	pub fn save(&self) -> Result<(), anyhow::Error> {
+		if self.read_only {
+			return Err(anyhow::anyhow!("{} was written by a newer version and will not be overwritten", self.path.display()));
+		}
+
 		fs::create_dir_all(self.path.parent().unwrap())?;
 
 		let contents = serde_json::to_string_pretty(&T::into_value(&self.value)?)?;
@@ -140,6 +217,19 @@ pub struct Settings {
 	pub separatewine: bool,
 	pub developer: bool,
 	pub disableelgato: bool,
+	pub plugin_restart_limit: u8,
+	pub loopback_only: bool,
+	pub require_plugin_tokens: bool,
+	pub api_enabled: bool,
+	pub api_port: u16,
+	pub api_token: String,
+	pub mqtt_enabled: bool,
+	pub mqtt_host: String,
+	pub mqtt_port: u16,
+	pub mqtt_username: String,
+	pub mqtt_password: String,
+	pub mqtt_topic_prefix: String,
+	pub trusted_plugin_keys: Vec<String>,
 }
 
 impl Default for Settings {
@@ -159,6 +249,19 @@ impl Default for Settings {
 			separatewine: false,
 			developer: false,
 			disableelgato: false,
+			plugin_restart_limit: 5,
+			loopback_only: false,
+			require_plugin_tokens: false,
+			api_enabled: false,
+			api_port: 57120,
+			api_token: String::new(),
+			mqtt_enabled: false,
+			mqtt_host: "localhost".to_owned(),
+			mqtt_port: 1883,
+			mqtt_username: String::new(),
+			mqtt_password: String::new(),
+			mqtt_topic_prefix: "opendeck".to_owned(),
+			trusted_plugin_keys: vec![],
 		}
 	}
 }
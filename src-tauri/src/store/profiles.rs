@@ -1,6 +1,6 @@
 use super::Store;
 
-use crate::shared::{ActionInstance, DEVICES, DeviceInfo, Profile, config_dir, copy_dir, initialise_encoder_layout};
+use crate::shared::{ActionInstance, DEVICES, DeviceInfo, PAGE_SEPARATOR, Profile, config_dir, copy_dir, initialise_encoder_layout, page_root};
 
 use std::collections::HashMap;
 use std::fs;
@@ -55,15 +55,7 @@ impl ProfileStores {
 				instance.action.plugin == "opendeck"
 					|| (plugins_dir.join(&instance.action.plugin).exists() && (!registered.contains(&instance.action.plugin) || actions.iter().any(|v| v.uuid == instance.action.uuid)))
 			};
-			for slot in store.value.keys.iter_mut().chain(store.value.sliders.iter_mut()).chain(store.value.infobars.iter_mut()) {
-				if let Some(instance) = slot {
-					if !keep_instance(instance) {
-						*slot = None;
-					} else if let Some(children) = &mut instance.children {
-						children.retain_mut(|child| keep_instance(child));
-					}
-				}
-			}
+			retain_instances(&mut store.value, &keep_instance);
 
 			// We need to populate instances from a profile without encoders or without parsed layouts with them
 			for instance in store.value.sliders.iter_mut().flatten() {
@@ -85,6 +77,17 @@ impl ProfileStores {
 		}
 	}
 
+	/// Get a page of a profile, which is either the profile itself or a folder page nested within it.
+	pub fn get_page(&self, device: &DeviceInfo, id: &str) -> Result<&Profile, anyhow::Error> {
+		let store = self.get_profile_store(device, page_root(id))?;
+		store.value.page(id).ok_or_else(|| anyhow!("page not found"))
+	}
+
+	pub async fn get_page_mut(&mut self, device: &DeviceInfo, id: &str) -> Result<&mut Profile, anyhow::Error> {
+		let store = self.get_profile_store_mut(device, page_root(id)).await?;
+		store.value.page_mut(id).ok_or_else(|| anyhow!("page not found"))
+	}
+
 	pub fn remove_profile(&mut self, device: &str, id: &str) {
 		self.stores.remove(&Self::canonical_id(device, id));
 	}
@@ -103,6 +106,11 @@ impl ProfileStores {
 	}
 
 	pub async fn rename_profile(&mut self, device: &DeviceInfo, old_id: &str, new_id: &str, retain: bool) -> Result<(), anyhow::Error> {
+		// Folder pages are addressed by appending the separator to the ID of their profile, so it cannot be part of a new profile name,
+		// although profiles named before folder pages were added may still be renamed away from such names.
+		if new_id.contains(PAGE_SEPARATOR) {
+			return Err(anyhow!("profile names cannot contain \"{PAGE_SEPARATOR}\""));
+		}
 		if !retain {
 			// Remove from the store but don't delete the file
 			self.remove_profile(&device.id, old_id);
@@ -173,19 +181,27 @@ impl ProfileStores {
 	pub fn all_from_plugin(&self, plugin: &str) -> Vec<crate::shared::ActionContext> {
 		let mut all = vec![];
 		for store in self.stores.values() {
-			for instance in store.value.keys.iter().chain(&store.value.sliders).chain(&store.value.infobars).flatten() {
-				if instance.action.plugin == plugin {
-					all.push(instance.context.clone());
-				} else if let Some(children) = &instance.children {
-					for child in children {
-						if child.action.plugin == plugin {
-							all.push(child.context.clone());
-						}
-					}
+			all.extend(store.value.all_instances().filter(|instance| instance.action.plugin == plugin).map(|instance| instance.context.clone()));
+		}
+		all
+	}
+}
+
+/// Remove instances that should not be kept from a page and the folder pages nested within it.
+fn retain_instances(page: &mut Profile, keep: &dyn Fn(&ActionInstance) -> bool) {
+	for slot in page.keys.iter_mut().chain(page.sliders.iter_mut()).chain(page.infobars.iter_mut()) {
+		if let Some(instance) = slot {
+			if !keep(instance) {
+				*slot = None;
+			} else {
+				if let Some(children) = &mut instance.children {
+					children.retain_mut(|child| keep(child));
+				}
+				if let Some(page) = &mut instance.page {
+					retain_instances(page, keep);
 				}
 			}
 		}
-		all
 	}
 }
 
@@ -198,6 +214,8 @@ impl super::NotProfile for DeviceConfig {}
 
 pub struct DeviceStores {
 	stores: HashMap<String, Store<DeviceConfig>>,
+	/// The folder page open on each device, which is not persisted between launches.
+	open_pages: HashMap<String, String>,
 }
 
 impl DeviceStores {
@@ -215,10 +233,23 @@ impl DeviceStores {
 
 		let from_store = &self.stores.get(device).unwrap().value.selected_profile;
 		let all = get_device_profiles(device)?;
-		if all.contains(from_store) { Ok(from_store.clone()) } else { Ok(all.first().unwrap().clone()) }
+		let selected = if all.contains(from_store) { from_store.clone() } else { all.first().unwrap().clone() };
+
+		match self.open_pages.get(device) {
+			Some(page) if page_root(page) == selected => Ok(page.clone()),
+			_ => Ok(selected),
+		}
 	}
 
-	pub fn set_selected_profile(&mut self, device: &str, id: String) -> Result<(), anyhow::Error> {
+	/// Select a profile or a folder page within one, of which only the profile is persisted.
+	pub fn set_selected_profile(&mut self, device: &str, mut id: String) -> Result<(), anyhow::Error> {
+		if id.contains(PAGE_SEPARATOR) {
+			self.open_pages.insert(device.to_owned(), id.clone());
+			id = page_root(&id).to_owned();
+		} else {
+			self.open_pages.remove(device);
+		}
+
 		if self.stores.contains_key(device) {
 			let store = self.stores.get_mut(device).unwrap();
 			store.value.selected_profile = id;
@@ -235,6 +266,55 @@ impl DeviceStores {
 	}
 }
 
+/// Settings for a single device, each of which overrides the global setting of the same name when present.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceSettings {
+	pub brightness: Option<u8>,
+	pub rotation: Option<u16>,
+	pub sleep_timeout_minutes: Option<u16>,
+}
+
+impl super::NotProfile for DeviceSettings {}
+
+impl DeviceSettings {
+	pub fn brightness(&self) -> u8 {
+		self.brightness.unwrap_or_else(|| super::get_settings().value.brightness)
+	}
+}
+
+pub struct DeviceSettingsStores {
+	stores: HashMap<String, Store<DeviceSettings>>,
+}
+
+impl DeviceSettingsStores {
+	pub fn get(&mut self, device: &str) -> Result<&DeviceSettings, anyhow::Error> {
+		if !self.stores.contains_key(device) {
+			let store = Store::new(device, &config_dir().join("devices"), DeviceSettings::default()).context(format!("Failed to create store for device settings {}", device))?;
+			self.stores.insert(device.to_owned(), store);
+		}
+		Ok(&self.stores.get(device).unwrap().value)
+	}
+
+	pub fn set(&mut self, device: &str, settings: DeviceSettings) -> Result<(), anyhow::Error> {
+		self.get(device)?;
+		let store = self.stores.get_mut(device).unwrap();
+		store.value = settings;
+		store.save()
+	}
+}
+
+/// Get the settings of a device, falling back to the defaults if they cannot be read.
+pub async fn get_device_settings(device: &str) -> DeviceSettings {
+	match DEVICE_SETTINGS.write().await.get(device) {
+		Ok(settings) => settings.clone(),
+		Err(error) => {
+			log::warn!("{error:#}");
+			DeviceSettings::default()
+		}
+	}
+}
+
 pub fn get_device_profiles(device: &str) -> Result<Vec<String>, anyhow::Error> {
 	let mut profiles: Vec<String> = vec![];
 
@@ -286,7 +366,15 @@ pub fn get_device_profiles(device: &str) -> Result<Vec<String>, anyhow::Error> {
 pub static PROFILE_STORES: LazyLock<RwLock<ProfileStores>> = LazyLock::new(|| RwLock::new(ProfileStores { stores: HashMap::new() }));
 
 /// A singleton object to manage Store instances for device configurations.
-pub static DEVICE_STORES: LazyLock<RwLock<DeviceStores>> = LazyLock::new(|| RwLock::new(DeviceStores { stores: HashMap::new() }));
+pub static DEVICE_STORES: LazyLock<RwLock<DeviceStores>> = LazyLock::new(|| {
+	RwLock::new(DeviceStores {
+		stores: HashMap::new(),
+		open_pages: HashMap::new(),
+	})
+});
+
+/// A singleton object to manage Store instances for per-device settings.
+pub static DEVICE_SETTINGS: LazyLock<RwLock<DeviceSettingsStores>> = LazyLock::new(|| RwLock::new(DeviceSettingsStores { stores: HashMap::new() }));
 
 pub struct Locks<'a> {
 	#[allow(dead_code)]
@@ -305,6 +393,42 @@ pub struct LocksMut<'a> {
 	pub profile_stores: RwLockWriteGuard<'a, ProfileStores>,
 }
 
+/// Rename the profiles of a device whose IDs contain the page separator, which was allowed before folder pages were added,
+/// so that they are not mistaken for folder pages.
+pub async fn rename_legacy_profiles(device: &DeviceInfo) {
+	let Ok(mut profiles) = get_device_profiles(&device.id) else { return };
+	profiles.sort();
+	profiles.dedup();
+	let legacy = profiles.iter().filter(|id| id.contains(PAGE_SEPARATOR)).cloned().collect::<Vec<_>>();
+	if legacy.is_empty() {
+		return;
+	}
+
+	let mut locks = acquire_locks_mut().await;
+	let selected = locks.device_stores.get_selected_profile(&device.id).ok();
+	for old_id in legacy {
+		let base = old_id.replace(PAGE_SEPARATOR, "-");
+		let mut new_id = base.clone();
+		let mut suffix = 2;
+		while profiles.contains(&new_id) {
+			new_id = format!("{base} {suffix}");
+			suffix += 1;
+		}
+
+		if let Err(error) = locks.profile_stores.rename_profile(device, &old_id, &new_id, false).await {
+			log::error!("Failed to rename profile {old_id} of device {}: {error:#}", device.id);
+			continue;
+		}
+		log::warn!("Renamed profile {old_id} of device {} to {new_id} as profile names cannot contain \"{PAGE_SEPARATOR}\"", device.id);
+		if selected.as_ref() == Some(&old_id)
+			&& let Err(error) = locks.device_stores.set_selected_profile(&device.id, new_id.clone())
+		{
+			log::error!("Failed to select renamed profile {new_id} of device {}: {error:#}", device.id);
+		}
+		profiles.push(new_id);
+	}
+}
+
 pub async fn acquire_locks_mut() -> LocksMut<'static> {
 	let device_stores = DEVICE_STORES.write().await;
 	let profile_stores = PROFILE_STORES.write().await;
@@ -313,12 +437,12 @@ pub async fn acquire_locks_mut() -> LocksMut<'static> {
 
 pub async fn get_slot<'a>(context: &crate::shared::Context, locks: &'a Locks<'_>) -> Result<&'a Option<crate::shared::ActionInstance>, anyhow::Error> {
 	let device = DEVICES.get(&context.device).ok_or_else(|| anyhow!("device not found"))?;
-	let store = locks.profile_stores.get_profile_store(&device, &context.profile)?;
+	let page = locks.profile_stores.get_page(&device, &context.profile)?;
 
 	let configured = match &context.controller[..] {
-		"Encoder" => store.value.sliders.get(context.position as usize).ok_or_else(|| anyhow!("index out of bounds"))?,
-		"Infobar" => store.value.infobars.get(context.position as usize).ok_or_else(|| anyhow!("index out of bounds"))?,
-		_ => store.value.keys.get(context.position as usize).ok_or_else(|| anyhow!("index out of bounds"))?,
+		"Encoder" => page.sliders.get(context.position as usize).ok_or_else(|| anyhow!("index out of bounds"))?,
+		"Infobar" => page.infobars.get(context.position as usize).ok_or_else(|| anyhow!("index out of bounds"))?,
+		_ => page.keys.get(context.position as usize).ok_or_else(|| anyhow!("index out of bounds"))?,
 	};
 
 	Ok(configured)
@@ -326,12 +450,12 @@ pub async fn get_slot<'a>(context: &crate::shared::Context, locks: &'a Locks<'_>
 
 pub async fn get_slot_mut<'a>(context: &crate::shared::Context, locks: &'a mut LocksMut<'_>) -> Result<&'a mut Option<crate::shared::ActionInstance>, anyhow::Error> {
 	let device = DEVICES.get(&context.device).ok_or_else(|| anyhow!("device not found"))?;
-	let store = locks.profile_stores.get_profile_store_mut(&device, &context.profile).await?;
+	let page = locks.profile_stores.get_page_mut(&device, &context.profile).await?;
 
 	let configured = match &context.controller[..] {
-		"Encoder" => store.value.sliders.get_mut(context.position as usize).ok_or_else(|| anyhow!("index out of bounds"))?,
-		"Infobar" => store.value.infobars.get_mut(context.position as usize).ok_or_else(|| anyhow!("index out of bounds"))?,
-		_ => store.value.keys.get_mut(context.position as usize).ok_or_else(|| anyhow!("index out of bounds"))?,
+		"Encoder" => page.sliders.get_mut(context.position as usize).ok_or_else(|| anyhow!("index out of bounds"))?,
+		"Infobar" => page.infobars.get_mut(context.position as usize).ok_or_else(|| anyhow!("index out of bounds"))?,
+		_ => page.keys.get_mut(context.position as usize).ok_or_else(|| anyhow!("index out of bounds"))?,
 	};
 
 	Ok(configured)
@@ -372,7 +496,7 @@ pub async fn get_instance_mut<'a>(context: &crate::shared::ActionContext, locks:
 pub async fn mark_profile_stale(device_id: &str, locks: &mut LocksMut<'_>) -> Result<(), anyhow::Error> {
 	let selected_profile = locks.device_stores.get_selected_profile(device_id)?;
 	let device = DEVICES.get(device_id).ok_or_else(|| anyhow!("device not found"))?;
-	let store = locks.profile_stores.get_profile_store_mut(&device, &selected_profile).await?;
+	let store = locks.profile_stores.get_profile_store_mut(&device, page_root(&selected_profile)).await?;
 	store.value.stale = true;
 	Ok(())
 }
@@ -380,7 +504,7 @@ pub async fn mark_profile_stale(device_id: &str, locks: &mut LocksMut<'_>) -> Re
 pub async fn save_profile_now(device_id: &str, locks: &mut LocksMut<'_>) -> Result<(), anyhow::Error> {
 	let selected_profile = locks.device_stores.get_selected_profile(device_id)?;
 	let device = DEVICES.get(device_id).ok_or_else(|| anyhow!("device not found"))?;
-	let store = locks.profile_stores.get_profile_store_mut(&device, &selected_profile).await?;
+	let store = locks.profile_stores.get_profile_store_mut(&device, page_root(&selected_profile)).await?;
 
 	store.save()?;
 	store.value.stale = false;
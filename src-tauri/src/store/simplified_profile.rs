@@ -1,6 +1,6 @@
 //! Duplicates of many structs to facilitate saving profiles to disk in a format that can be transferred between devices or systems.
 
-use crate::shared::{Action, ActionContext, ActionInstance, ActionState, Profile};
+use crate::shared::{Action, ActionContext, ActionInstance, ActionState, PAGE_SEPARATOR, Profile, page_images_path};
 
 use std::{
 	fs,
@@ -68,13 +68,19 @@ pub struct DiskActionInstance {
 	pub current_state: u16,
 	pub settings: serde_json::Value,
 	pub children: Option<Vec<DiskActionInstance>>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub page: Option<DiskProfile>,
 }
 
 impl From<ActionInstance> for DiskActionInstance {
 	fn from(mut value: ActionInstance) -> Self {
 		let disk_context: DiskActionContext = value.context.clone().into();
 		let config_dir = crate::shared::config_dir();
-		let image_dir = config_dir.join("images").join(&value.context.device).join(&value.context.profile).join(disk_context.to_string());
+		let image_dir = config_dir
+			.join("images")
+			.join(&value.context.device)
+			.join(page_images_path(&value.context.profile))
+			.join(disk_context.to_string());
 
 		let normalise_path = |value: &str| -> String {
 			let path = Path::new(value);
@@ -133,17 +139,19 @@ impl From<ActionInstance> for DiskActionInstance {
 			current_state: value.current_state,
 			settings: value.settings,
 			children: value.children.map(|c| c.into_iter().map(|v| v.into()).collect()),
+			page: value.page.map(|v| (&*v).into()),
 		}
 	}
 }
 
 impl DiskActionInstance {
-	fn into_action_instance(self, path: &Path) -> ActionInstance {
+	/// Convert an instance loaded from the profile at `path`, located on the page of that profile identified by the suffix `page`.
+	fn into_action_instance(self, path: &Path, page: &str) -> ActionInstance {
 		let config_dir = crate::shared::config_dir();
 		let mut iter = path.strip_prefix(&config_dir).unwrap().iter();
 		let device = iter.nth(1).unwrap().to_string_lossy().into_owned();
 		let mut profile = iter.map(|x| x.to_string_lossy()).collect::<Vec<_>>().join("/");
-		profile = profile[..profile.len() - 5].to_owned();
+		profile = profile[..profile.len() - 5].to_owned() + page;
 
 		let reconstruct_path = |value: &str| -> String {
 			if !(value.is_empty() || value.starts_with("data:") || value.starts_with("opendeck/")) {
@@ -159,7 +167,7 @@ impl DiskActionInstance {
 				state.image = config_dir
 					.join("images")
 					.join(&device)
-					.join(&profile)
+					.join(page_images_path(&profile))
 					.join(self.context.to_string())
 					.join(&state.image)
 					.to_string_lossy()
@@ -175,13 +183,15 @@ impl DiskActionInstance {
 		action.icon = reconstruct_path(&action.icon);
 		action.property_inspector = reconstruct_path(&action.property_inspector);
 
+		let folder_page = format!("{page}{PAGE_SEPARATOR}{}", self.context.position);
 		ActionInstance {
 			context: self.context.into_action_context(device, profile),
 			action,
 			states,
 			current_state: self.current_state,
 			settings: self.settings,
-			children: self.children.map(|c| c.into_iter().map(|v| v.into_action_instance(path)).collect()),
+			children: self.children.map(|c| c.into_iter().map(|v| v.into_action_instance(path, page)).collect()),
+			page: self.page.map(|v| Box::new(v.into_page(path, &folder_page))),
 		}
 	}
 }
@@ -205,17 +215,18 @@ impl From<&Profile> for DiskProfile {
 }
 
 impl DiskProfile {
-	fn into_profile(self, path: &Path) -> Profile {
+	/// Convert the profile at `path`, or the page of it identified by the suffix `page`.
+	fn into_page(self, path: &Path, page: &str) -> Profile {
 		let config_dir = crate::shared::config_dir();
 		let mut iter = path.strip_prefix(config_dir).unwrap().iter();
 		let _ = iter.nth(1);
 		let mut id = iter.map(|x| x.to_string_lossy()).collect::<Vec<_>>().join("/");
-		id = id[..id.len() - 5].to_owned();
+		id = id[..id.len() - 5].to_owned() + page;
 		Profile {
 			id,
-			keys: self.keys.into_iter().map(|x| x.map(|v| v.into_action_instance(path))).collect(),
-			sliders: self.sliders.into_iter().map(|x| x.map(|v| v.into_action_instance(path))).collect(),
-			infobars: self.infobars.into_iter().map(|x| x.map(|v| v.into_action_instance(path))).collect(),
+			keys: self.keys.into_iter().map(|x| x.map(|v| v.into_action_instance(path, page))).collect(),
+			sliders: self.sliders.into_iter().map(|x| x.map(|v| v.into_action_instance(path, page))).collect(),
+			infobars: self.infobars.into_iter().map(|x| x.map(|v| v.into_action_instance(path, page))).collect(),
 
 			stale: false,
 		}
@@ -223,12 +234,17 @@ impl DiskProfile {
 }
 
 impl super::FromAndIntoDiskValue for Profile {
+	const MIGRATIONS: &'static [super::migrations::Migration] = super::migrations::PROFILE_MIGRATIONS;
+
 	fn into_value(&self) -> Result<serde_json::Value, serde_json::Error> {
 		let disk: DiskProfile = self.into();
-		serde_json::to_value(disk)
+		let mut value = serde_json::to_value(disk)?;
+		super::migrations::stamp(&mut value, super::migrations::PROFILE_MIGRATIONS);
+		Ok(value)
 	}
-	fn from_value(value: serde_json::Value, path: &Path) -> Result<Profile, serde_json::Error> {
+	fn from_value(mut value: serde_json::Value, path: &Path) -> Result<Profile, serde_json::Error> {
+		super::migrations::migrate(&mut value, super::migrations::PROFILE_MIGRATIONS)?;
 		let disk: DiskProfile = serde_json::from_value(value)?;
-		Ok(disk.into_profile(path))
+		Ok(disk.into_page(path, ""))
 	}
 }
@@ -0,0 +1,627 @@
+//! Conversion between OpenDeck profiles and the `.streamDeckProfile` archives used by the Elgato Stream Deck software.
+
+use crate::shared::{Action, ActionContext, ActionInstance, ActionState, DeviceInfo, FontSize, PAGE_SEPARATOR, Profile};
+
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Read, Write};
+
+use base64::Engine as _;
+use elgato_streamdeck::info::Kind;
+use serde::Serialize;
+use serde_json::{Value, json};
+
+const MULTI_ACTION: &str = "com.elgato.streamdeck.multiactions.routine";
+const MULTI_ACTION_SWITCH: &str = "com.elgato.streamdeck.multiactions.routine2";
+const MULTI_ACTION_DELAY: &str = "com.elgato.streamdeck.multiactions.delay";
+const OPEN_FOLDER: &str = "com.elgato.streamdeck.profile.openchild";
+const BACK_TO_PARENT: &str = "com.elgato.streamdeck.profile.backtoparent";
+
+/// The maximum number of levels of profile archives that may be nested inside a backup.
+const MAX_ARCHIVE_DEPTH: u8 = 2;
+/// The maximum total size of the files extracted from an archive, including those of nested archives.
+const MAX_ARCHIVE_SIZE: u64 = 256 * 1024 * 1024;
+
+#[derive(Default, Serialize)]
+pub struct ImportReport {
+	pub profiles: Vec<String>,
+	pub skipped_actions: Vec<String>,
+}
+
+/// The files of an archive, keyed by their path with forward slashes.
+struct Archive(HashMap<String, Vec<u8>>);
+
+impl Archive {
+	fn read(bytes: &[u8]) -> Result<Self, anyhow::Error> {
+		let mut files = HashMap::new();
+		let mut remaining = MAX_ARCHIVE_SIZE;
+		Self::read_into(bytes, "", 0, &mut remaining, &mut files)?;
+		Ok(Self(files))
+	}
+
+	fn read_into(bytes: &[u8], prefix: &str, depth: u8, remaining: &mut u64, files: &mut HashMap<String, Vec<u8>>) -> Result<(), anyhow::Error> {
+		let mut archive = zip::ZipArchive::new(Cursor::new(bytes))?;
+		for i in 0..archive.len() {
+			let mut file = archive.by_index(i)?;
+			if file.is_dir() {
+				continue;
+			}
+			let name = format!("{prefix}{}", file.name().replace('\\', "/"));
+			let mut contents = vec![];
+			// Read one byte past the limit so that archives which exceed it can be told apart from those which reach it exactly.
+			(&mut file).take(*remaining + 1).read_to_end(&mut contents)?;
+			if contents.len() as u64 > *remaining {
+				return Err(anyhow::anyhow!("archive is larger than {} MiB when extracted", MAX_ARCHIVE_SIZE / 1024 / 1024));
+			}
+			*remaining -= contents.len() as u64;
+			// Backups may contain complete profile archives rather than their extracted contents.
+			if name.to_lowercase().ends_with(".streamdeckprofile") {
+				if depth >= MAX_ARCHIVE_DEPTH {
+					return Err(anyhow::anyhow!("archive contains profile archives nested more than {MAX_ARCHIVE_DEPTH} levels deep"));
+				}
+				Self::read_into(&contents, &format!("{name}/"), depth + 1, remaining, files)?;
+			} else {
+				files.insert(name, contents);
+			}
+		}
+		Ok(())
+	}
+
+	fn get(&self, path: &str) -> Option<&Vec<u8>> {
+		self.0.get(path).or_else(|| self.0.iter().find(|(k, _)| k.eq_ignore_ascii_case(path)).map(|(_, v)| v))
+	}
+
+	fn json(&self, path: &str) -> Option<Value> {
+		let bytes = self.get(path)?;
+		serde_json::from_slice(bytes.strip_prefix(&[0xEF, 0xBB, 0xBF][..]).unwrap_or(bytes)).ok()
+	}
+
+	/// Find the directory of every profile in the archive, excluding the folder pages nested within them.
+	fn profile_roots(&self) -> Vec<String> {
+		let mut roots = self
+			.0
+			.keys()
+			.filter_map(|path| path.strip_suffix("manifest.json"))
+			.filter(|dir| {
+				let lower = dir.to_lowercase();
+				(lower.is_empty() || lower.ends_with(".sdprofile/")) && lower.matches(".sdprofile/").count() <= 1
+			})
+			.filter(|dir| self.json(&format!("{dir}manifest.json")).is_some_and(|v| v.get("Name").is_some()))
+			.map(|dir| dir.to_owned())
+			.collect::<Vec<_>>();
+		roots.sort();
+		roots
+	}
+
+	/// Find the directory of a page within a profile given its identifier.
+	fn page_dir(&self, root: &str, id: &str) -> Option<String> {
+		[format!("{root}Profiles/{id}/"), format!("{root}Profiles/{id}.sdProfile/")]
+			.into_iter()
+			.find(|dir| self.get(&format!("{dir}manifest.json")).is_some())
+	}
+
+	/// Load an image referenced by an action as a data URL.
+	fn image(&self, page_dir: &str, key: &str, image: &str) -> Option<String> {
+		if image.starts_with("data:") {
+			return Some(image.to_owned());
+		}
+
+		let bytes = [format!("{page_dir}{image}"), format!("{page_dir}{key}/{image}"), format!("{page_dir}Images/{image}")]
+			.iter()
+			.find_map(|path| self.get(path))?;
+		let mime = match image.rsplit('.').next().map(|v| v.to_lowercase()).as_deref() {
+			Some("svg") => "image/svg+xml",
+			Some("jpg" | "jpeg") => "image/jpeg",
+			Some("gif") => "image/gif",
+			Some("webp") => "image/webp",
+			_ => "image/png",
+		};
+		Some(format!("data:{mime};base64,{}", base64::engine::general_purpose::STANDARD.encode(bytes)))
+	}
+}
+
+/// Get the actions of a page, along with the controller and coordinates of each.
+fn page_actions(manifest: &Value) -> Vec<(String, String, Value)> {
+	let mut actions = vec![];
+	if let Some(controllers) = manifest.get("Controllers").and_then(Value::as_array) {
+		for controller in controllers {
+			let kind = controller.get("Type").and_then(Value::as_str).unwrap_or("Keypad");
+			for (key, action) in controller.get("Actions").and_then(Value::as_object).into_iter().flatten() {
+				actions.push((kind.to_owned(), key.clone(), action.clone()));
+			}
+		}
+	} else if let Some(keys) = manifest.get("Actions").and_then(Value::as_object) {
+		for (key, action) in keys {
+			actions.push(("Keypad".to_owned(), key.clone(), action.clone()));
+		}
+	}
+	actions
+}
+
+/// Apply the title and font properties of a state from a profile to a state of an action.
+fn overlay_state(state: &mut ActionState, value: &Value) {
+	let string = |field: &str| value.get(field).and_then(Value::as_str).filter(|v| !v.is_empty()).map(str::to_owned);
+	let boolean = |field: &str| value.get(field).and_then(Value::as_bool);
+
+	if let Some(title) = value.get("Title").and_then(Value::as_str) {
+		title.clone_into(&mut state.text);
+	}
+	if let Some(show) = boolean("ShowTitle") {
+		state.show = show;
+	}
+	if let Some(colour) = string("TitleColor") {
+		state.colour = colour;
+	}
+	if let Some(alignment) = string("TitleAlignment") {
+		state.alignment = alignment;
+	}
+	if let Some(family) = string("FontFamily") {
+		state.family = family;
+	}
+	if let Some(style) = string("FontStyle") {
+		state.style = style;
+	}
+	if let Some(size) = value.get("FontSize").and_then(|v| serde_json::from_value::<FontSize>(v.clone()).ok()) {
+		state.size = size;
+	}
+	if let Some(underline) = boolean("FontUnderline") {
+		state.underline = underline;
+	}
+}
+
+fn sanitise_name(name: &str) -> String {
+	let name = name.replace(['/', '\\', '<', '>', ':', '"', '|', '?', '*', PAGE_SEPARATOR], "-").trim().to_owned();
+	if name.is_empty() { "Imported".to_owned() } else { name }
+}
+
+struct Importer<'a> {
+	archive: &'a Archive,
+	actions: &'a [Action],
+	device: &'a DeviceInfo,
+	taken: Vec<String>,
+	visited: HashSet<String>,
+	root: String,
+	profiles: Vec<Profile>,
+	report: ImportReport,
+}
+
+impl Importer<'_> {
+	fn unique_id(&mut self, id: String) -> String {
+		let mut candidate = id.clone();
+		let mut counter = 2;
+		while self.taken.iter().any(|v| v.eq_ignore_ascii_case(&candidate)) {
+			candidate = format!("{id} {counter}");
+			counter += 1;
+		}
+		self.taken.push(candidate.clone());
+		candidate
+	}
+
+	fn find_action(&self, uuid: &str) -> Option<Action> {
+		self.actions.iter().find(|v| v.uuid == uuid).cloned()
+	}
+
+	fn position(&self, controller: &str, key: &str) -> Option<u8> {
+		let (column, row) = key.split_once(',')?;
+		let (column, row) = (column.trim().parse::<u8>().ok()?, row.trim().parse::<u8>().ok()?);
+		match controller {
+			"Keypad" if column < self.device.columns && row < self.device.rows => Some(row * self.device.columns + column),
+			"Encoder" if column < self.device.encoders => Some(column),
+			_ => None,
+		}
+	}
+
+	fn import_page(&mut self, page_dir: &str, manifest: &Value, id: String) -> Profile {
+		let device = self.device;
+		let mut profile = Profile {
+			id: id.clone(),
+			keys: vec![None; (device.rows * device.columns + device.touchpoints) as usize],
+			sliders: vec![None; device.encoders as usize],
+			infobars: vec![None; device.infobars as usize],
+			stale: false,
+		};
+
+		for (controller, key, value) in page_actions(manifest) {
+			let Some(position) = self.position(&controller, &key) else { continue };
+			let context = ActionContext {
+				device: device.id.clone(),
+				profile: id.clone(),
+				controller: controller.clone(),
+				position,
+				index: 0,
+			};
+			let instance = self.convert(&value, context, page_dir, &key);
+			match controller.as_str() {
+				"Encoder" => profile.sliders[position as usize] = instance,
+				_ => profile.keys[position as usize] = instance,
+			}
+		}
+
+		profile
+	}
+
+	fn states(&self, action: &Action, value: &Value, page_dir: &str, key: &str) -> Vec<ActionState> {
+		let mut states = action.states.clone();
+		for (index, (state, value)) in states.iter_mut().zip(value.get("States").and_then(Value::as_array).into_iter().flatten()).enumerate() {
+			overlay_state(state, value);
+			let image = match value.get("Image").and_then(Value::as_str) {
+				Some(image) if !image.is_empty() => image.to_owned(),
+				_ => format!("CustomImages/state{index}.png"),
+			};
+			if let Some(image) = self.archive.image(page_dir, key, &image) {
+				state.image = image;
+			}
+		}
+		states
+	}
+
+	fn convert(&mut self, value: &Value, context: ActionContext, page_dir: &str, key: &str) -> Option<ActionInstance> {
+		let uuid = value.get("UUID").and_then(Value::as_str)?;
+		let name = value.get("Name").and_then(Value::as_str).unwrap_or(uuid).to_owned();
+
+		match uuid {
+			MULTI_ACTION | MULTI_ACTION_SWITCH => {
+				let is_multi_action = uuid == MULTI_ACTION;
+				let action = self.find_action(if is_multi_action { "opendeck.multiaction" } else { "opendeck.toggleaction" })?;
+				let groups = value.get("Actions").and_then(Value::as_array).cloned().unwrap_or_default();
+				let sequences = groups.iter().map(|g| g.get("Actions").and_then(Value::as_array).cloned().unwrap_or_default());
+				// Multi Action Switch states each hold a sequence, of which only the first action can be represented by a Toggle Action.
+				let steps = if is_multi_action {
+					sequences.flatten().collect::<Vec<_>>()
+				} else {
+					let mut steps = vec![];
+					for sequence in sequences {
+						let mut sequence = sequence.into_iter();
+						steps.extend(sequence.next());
+						for dropped in sequence.filter(|v| v.get("UUID").and_then(Value::as_str) != Some(MULTI_ACTION_DELAY)) {
+							let uuid = dropped.get("UUID").and_then(Value::as_str).unwrap_or_default();
+							let dropped_name = dropped.get("Name").and_then(Value::as_str).unwrap_or(uuid);
+							self.report.skipped_actions.push(format!("{dropped_name} ({uuid}) in {name}"));
+						}
+					}
+					steps
+				};
+
+				let mut children: Vec<ActionInstance> = vec![];
+				let mut delays: Vec<u64> = vec![];
+				for step in steps {
+					if step.get("UUID").and_then(Value::as_str) == Some(MULTI_ACTION_DELAY) {
+						let settings = step.get("Settings");
+						let delay = ["delayInMs", "DelayInMs", "delay"].iter().find_map(|k| settings.and_then(|s| s.get(*k)).and_then(Value::as_u64));
+						if let (Some(last), Some(delay)) = (delays.last_mut(), delay) {
+							*last = delay;
+						}
+						continue;
+					}
+					let child_context = ActionContext {
+						index: children.len() as u16 + 1,
+						..context.clone()
+					};
+					if let Some(child) = self.convert(&step, child_context, page_dir, key) {
+						children.push(child);
+						delays.push(100);
+					}
+				}
+
+				let mut states = action.states.clone();
+				if !is_multi_action {
+					while states.len() < children.len() {
+						states.push(ActionState {
+							image: "opendeck/toggle-action.png".to_owned(),
+							..Default::default()
+						});
+					}
+				}
+
+				Some(ActionInstance {
+					action,
+					context,
+					states,
+					current_state: 0,
+					settings: if is_multi_action { json!({ "delays": delays }) } else { json!({}) },
+					children: Some(children),
+					page: None,
+				})
+			}
+			OPEN_FOLDER => {
+				let child = value.get("Settings").and_then(|v| v.get("ProfileUUID")).and_then(Value::as_str)?;
+				let child_dir = self.archive.page_dir(&self.root, child)?;
+				if !self.visited.insert(child_dir.clone()) {
+					return None;
+				}
+				let manifest = self.archive.json(&format!("{child_dir}manifest.json"))?;
+
+				let action = self.find_action("opendeck.folder")?;
+				let page = self.import_page(&child_dir, &manifest, format!("{}{PAGE_SEPARATOR}{}", context.profile, context.position));
+				Some(ActionInstance {
+					states: self.states(&action, value, page_dir, key),
+					action,
+					context,
+					current_state: 0,
+					settings: json!({}),
+					children: None,
+					page: Some(Box::new(page)),
+				})
+			}
+			BACK_TO_PARENT => {
+				let action = self.find_action("opendeck.folderback")?;
+				Some(ActionInstance {
+					states: action.states.clone(),
+					action,
+					context,
+					current_state: 0,
+					settings: json!({}),
+					children: None,
+					page: None,
+				})
+			}
+			_ => {
+				let Some(action) = self.find_action(uuid) else {
+					self.report.skipped_actions.push(format!("{name} ({uuid})"));
+					return None;
+				};
+				let states = self.states(&action, value, page_dir, key);
+				let current_state = value.get("State").and_then(Value::as_u64).unwrap_or(0).min(states.len().saturating_sub(1) as u64) as u16;
+				Some(ActionInstance {
+					action,
+					context,
+					states,
+					current_state,
+					settings: value.get("Settings").filter(|v| v.is_object()).cloned().unwrap_or_else(|| json!({})),
+					children: None,
+					page: None,
+				})
+			}
+		}
+	}
+}
+
+/// Convert a `.streamDeckProfile` or `.streamDeckProfilesBackup` archive into profiles for a device.
+pub fn import(bytes: &[u8], device: &DeviceInfo, actions: &[Action], existing: Vec<String>) -> Result<(Vec<Profile>, ImportReport), anyhow::Error> {
+	let archive = Archive::read(bytes)?;
+	let roots = archive.profile_roots();
+	if roots.is_empty() {
+		return Err(anyhow::anyhow!("no Stream Deck profiles were found in the archive"));
+	}
+
+	let mut importer = Importer {
+		archive: &archive,
+		actions,
+		device,
+		taken: existing,
+		visited: HashSet::new(),
+		root: String::new(),
+		profiles: vec![],
+		report: ImportReport::default(),
+	};
+
+	for root in roots {
+		let Some(manifest) = archive.json(&format!("{root}manifest.json")) else { continue };
+		let name = sanitise_name(manifest.get("Name").and_then(Value::as_str).unwrap_or_default());
+		importer.root = root.clone();
+
+		// Newer profiles store their pages separately, and older profiles store the actions of their only page in the profile manifest.
+		let pages = manifest.get("Pages").and_then(|v| v.get("Pages")).and_then(Value::as_array).cloned().unwrap_or_default();
+		if pages.is_empty() {
+			let id = importer.unique_id(name);
+			importer.visited.insert(root.clone());
+			let profile = importer.import_page(&root, &manifest, id);
+			importer.profiles.push(profile);
+		} else {
+			for (index, page) in pages.iter().filter_map(Value::as_str).enumerate() {
+				let Some(page_dir) = archive.page_dir(&root, page) else { continue };
+				let Some(page_manifest) = archive.json(&format!("{page_dir}manifest.json")) else { continue };
+				let id = if index == 0 { name.clone() } else { format!("{name}/Page {}", index + 1) };
+				let id = importer.unique_id(id);
+				importer.visited.insert(page_dir.clone());
+				let profile = importer.import_page(&page_dir, &page_manifest, id);
+				importer.profiles.push(profile);
+			}
+		}
+	}
+
+	importer.report.profiles = importer.profiles.iter().map(|v| v.id.clone()).collect();
+	Ok((importer.profiles, importer.report))
+}
+
+fn random_id() -> String {
+	use rand::Rng;
+	let bytes: [u8; 16] = rand::rng().random();
+	let hex = bytes.iter().map(|b| format!("{b:02X}")).collect::<String>();
+	format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+struct Exporter {
+	root: String,
+	plugins_dir: std::path::PathBuf,
+	columns: u8,
+	rows: u8,
+	files: Vec<(String, Vec<u8>)>,
+}
+
+impl Exporter {
+	/// Add a custom image to the archive, returning its path relative to the page.
+	fn export_image(&mut self, page_dir: &str, plugin: &str, state: &ActionState, default: Option<&ActionState>) -> String {
+		if state.image.is_empty() || state.image.starts_with("opendeck/") || default.is_some_and(|v| v.image == state.image) {
+			return String::new();
+		}
+
+		let (extension, bytes) = if let Some((header, data)) = state.image.strip_prefix("data:").and_then(|v| v.split_once(',')) {
+			let extension = header.split(['/', ';', '+']).nth(1).unwrap_or("png").to_owned();
+			let bytes = if header.ends_with(";base64") {
+				match base64::engine::general_purpose::STANDARD.decode(data) {
+					Ok(bytes) => bytes,
+					Err(_) => return String::new(),
+				}
+			} else {
+				urlencoding::decode_binary(data.as_bytes()).into_owned()
+			};
+			(extension, bytes)
+		} else {
+			let extension = std::path::Path::new(&state.image)
+				.extension()
+				.map(|v| v.to_string_lossy().into_owned())
+				.unwrap_or_else(|| "png".to_owned());
+			// Images set by plugins may be given relative to the plugin's directory.
+			let path = std::path::Path::new(&state.image);
+			let path = if path.is_absolute() { path.to_path_buf() } else { self.plugins_dir.join(plugin).join(path) };
+			match std::fs::read(path) {
+				Ok(bytes) => (extension, bytes),
+				Err(_) => return String::new(),
+			}
+		};
+
+		let path = format!("Images/{}.{extension}", random_id());
+		self.files.push((format!("{page_dir}{path}"), bytes));
+		path
+	}
+
+	fn export_action(&mut self, page_dir: &str, instance: &ActionInstance) -> Value {
+		let (uuid, name) = match instance.action.uuid.as_str() {
+			"opendeck.multiaction" => (MULTI_ACTION, "Multi Action"),
+			"opendeck.toggleaction" => (MULTI_ACTION_SWITCH, "Multi Action Switch"),
+			"opendeck.folder" => (OPEN_FOLDER, "Create Folder"),
+			"opendeck.folderback" => (BACK_TO_PARENT, "Parent Folder"),
+			uuid => (uuid, instance.action.name.as_str()),
+		};
+
+		let states = instance
+			.states
+			.iter()
+			.enumerate()
+			.map(|(index, state)| {
+				json!({
+					"Image": self.export_image(page_dir, &instance.action.plugin, state, instance.action.states.get(index)),
+					"Title": state.text,
+					"ShowTitle": state.show,
+					"TitleColor": state.colour,
+					"TitleAlignment": state.alignment,
+					"FontFamily": state.family,
+					"FontSize": state.size.0,
+					"FontStyle": state.style,
+					"FontUnderline": state.underline,
+				})
+			})
+			.collect::<Vec<_>>();
+
+		let mut action = json!({
+			"Name": name,
+			"UUID": uuid,
+			"State": instance.current_state,
+			"States": states,
+			"Settings": instance.settings,
+		});
+		if instance.action.plugin != "opendeck" {
+			action["Plugin"] = json!({ "UUID": instance.action.plugin, "Name": instance.action.plugin, "Version": "" });
+		}
+
+		if let Some(children) = &instance.children {
+			let children = children.iter().map(|child| self.export_action(page_dir, child)).collect::<Vec<_>>();
+			action["Actions"] = if uuid == MULTI_ACTION {
+				json!([{ "Actions": children }])
+			} else {
+				Value::Array(children.into_iter().map(|child| json!({ "Actions": [child] })).collect())
+			};
+		}
+
+		if let Some(page) = &instance.page {
+			let id = random_id();
+			self.export_page(page, &id);
+			action["Settings"] = json!({ "ProfileUUID": id });
+		}
+
+		action
+	}
+
+	/// Add the manifest and images of a page to the archive.
+	fn export_page(&mut self, page: &Profile, id: &str) {
+		let page_dir = format!("{}Profiles/{id}/", self.root);
+
+		let mut keypad = serde_json::Map::new();
+		for (position, instance) in page.keys.iter().enumerate().take((self.rows * self.columns) as usize) {
+			if let Some(instance) = instance {
+				let key = format!("{},{}", position as u8 % self.columns, position as u8 / self.columns);
+				keypad.insert(key, self.export_action(&page_dir, instance));
+			}
+		}
+		let mut encoder = serde_json::Map::new();
+		for (position, instance) in page.sliders.iter().enumerate() {
+			if let Some(instance) = instance {
+				encoder.insert(format!("{position},0"), self.export_action(&page_dir, instance));
+			}
+		}
+
+		let manifest = json!({
+			"Name": "",
+			"Controllers": [
+				{ "Type": "Keypad", "Actions": keypad },
+				{ "Type": "Encoder", "Actions": encoder },
+			],
+		});
+		self.files.push((format!("{page_dir}manifest.json"), serde_json::to_vec_pretty(&manifest).unwrap_or_default()));
+	}
+}
+
+/// Get the model number that the Stream Deck software records in profiles for a kind of device.
+fn device_model(kind: Kind) -> &'static str {
+	match kind {
+		Kind::Original => "20GAA9901",
+		Kind::OriginalV2 => "20GAA9902",
+		Kind::Mk2 | Kind::Mk2Module => "20GBA9901",
+		Kind::Mk2Scissor => "20GBA9902",
+		Kind::Mini | Kind::MiniDiscord => "20GAI9901",
+		Kind::MiniMk2 | Kind::MiniMk2Module => "20GAI9902",
+		Kind::Xl => "20GAT9901",
+		Kind::XlV2 | Kind::XlV2Module => "20GAT9902",
+		Kind::Pedal => "20GBF9901",
+		Kind::Plus => "20GBD9901",
+		Kind::Neo => "20GBJ9901",
+		Kind::PlusXl => "20GBX9901",
+	}
+}
+
+/// Get the most common kind of device with a device type, for devices that are not connected or not made by Elgato.
+fn kind_of_type(device_type: u8) -> Option<Kind> {
+	match device_type {
+		0 => Some(Kind::Mk2),
+		1 => Some(Kind::Mini),
+		2 => Some(Kind::Xl),
+		5 => Some(Kind::Pedal),
+		7 => Some(Kind::Plus),
+		9 => Some(Kind::Neo),
+		13 => Some(Kind::PlusXl),
+		_ => None,
+	}
+}
+
+/// Convert a profile, including its folder pages, into a `.streamDeckProfile` archive.
+///
+/// `kind` is the kind of the device if it is a connected Elgato device, and is otherwise guessed from the device type.
+pub fn export(profile: &Profile, device: &DeviceInfo, kind: Option<Kind>) -> Result<Vec<u8>, anyhow::Error> {
+	let name = profile.id.rsplit('/').next().unwrap_or(&profile.id);
+	let page = random_id();
+
+	let mut exporter = Exporter {
+		root: format!("{}.sdProfile/", random_id()),
+		plugins_dir: crate::shared::config_dir().join("plugins"),
+		columns: device.columns,
+		rows: device.rows,
+		files: vec![],
+	};
+	exporter.export_page(profile, &page);
+
+	let manifest = json!({
+		"Name": name,
+		"Version": "2.0",
+		"Device": { "Model": kind.or_else(|| kind_of_type(device.r#type)).map(device_model).unwrap_or_default(), "UUID": device.id.strip_prefix("sd-").unwrap_or(&device.id) },
+		"Pages": { "Current": page, "Default": page, "Pages": [page] },
+	});
+	exporter.files.push((format!("{}manifest.json", exporter.root), serde_json::to_vec_pretty(&manifest)?));
+
+	let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+	let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+	for (path, bytes) in exporter.files {
+		zip.start_file(path, options)?;
+		zip.write_all(&bytes)?;
+	}
+	Ok(zip.finish()?.into_inner())
+}
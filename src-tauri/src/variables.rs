@@ -0,0 +1,176 @@
+//! Named global and per-profile variables, which can be referenced in action titles as `{{name}}` alongside `{{clock:%H:%M}}` for the current time.
+
+use crate::shared::{config_dir, page_root};
+use crate::store::{NotProfile, Store};
+
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use anyhow::bail;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager};
+use tokio::sync::RwLock;
+
+/// The name of the built-in variable that is replaced with the current time.
+const CLOCK: &str = "clock";
+const DEFAULT_CLOCK_FORMAT: &str = "%H:%M";
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Variables {
+	pub global: BTreeMap<String, String>,
+	/// Variables that take precedence over global variables of the same name on a single profile, keyed by device and then by profile.
+	pub profiles: BTreeMap<String, BTreeMap<String, BTreeMap<String, String>>>,
+}
+
+impl NotProfile for Variables {}
+
+pub static VARIABLES: LazyLock<RwLock<Store<Variables>>> = LazyLock::new(|| RwLock::new(Store::new("variables", &config_dir(), Variables::default()).unwrap()));
+
+/// Whether variables have changed since they were last saved, as plugins may update them many times a second.
+static STALE: AtomicBool = AtomicBool::new(false);
+
+/// Set or, when `value` is `None`, remove a variable.
+///
+/// The variable is set on a profile of a device when both are given, on the selected profile of a device when only the device is given, and globally otherwise.
+pub async fn set_variable(name: &str, value: Option<String>, device: Option<&str>, profile: Option<&str>) -> Result<(), anyhow::Error> {
+	if name.is_empty() || name == CLOCK || name.contains(['{', '}', ':']) {
+		bail!("invalid variable name \"{name}\"");
+	}
+	let profile = match (device, profile) {
+		(Some(device), Some(profile)) => Some((device.to_owned(), page_root(profile).to_owned())),
+		(Some(device), None) => {
+			if !crate::shared::DEVICES.contains_key(device) {
+				bail!("device {device} not found");
+			}
+			let selected = crate::store::profiles::DEVICE_STORES.write().await.get_selected_profile(device)?;
+			Some((device.to_owned(), page_root(&selected).to_owned()))
+		}
+		(None, Some(_)) => bail!("a device must be given to set a variable on a profile"),
+		(None, None) => None,
+	};
+
+	let mut store = VARIABLES.write().await;
+	let variables = match &profile {
+		Some((device, profile)) => store.value.profiles.entry(device.clone()).or_default().entry(profile.clone()).or_default(),
+		None => &mut store.value.global,
+	};
+	let changed = match value {
+		Some(value) => variables.insert(name.to_owned(), value.clone()).as_ref() != Some(&value),
+		None => variables.remove(name).is_some(),
+	};
+	store.value.profiles.retain(|_, profiles| {
+		profiles.retain(|_, variables| !variables.is_empty());
+		!profiles.is_empty()
+	});
+	drop(store);
+
+	if changed {
+		STALE.store(true, Ordering::Relaxed);
+		notify_changed(&[name]);
+	}
+	Ok(())
+}
+
+/// Remove the variables of a profile that has been deleted, or those of every profile of a device that has been deleted when `profile` is `None`.
+pub async fn forget(device: &str, profile: Option<&str>) -> Result<(), anyhow::Error> {
+	let mut store = VARIABLES.write().await;
+	let removed = match profile {
+		Some(profile) => {
+			let Some(profiles) = store.value.profiles.get_mut(device) else { return Ok(()) };
+			let removed = profiles.remove(profile).is_some();
+			if profiles.is_empty() {
+				store.value.profiles.remove(device);
+			}
+			removed
+		}
+		None => store.value.profiles.remove(device).is_some(),
+	};
+	if removed {
+		store.save()?;
+	}
+	Ok(())
+}
+
+/// Give a renamed profile the variables of its old ID, moving them unless the profile was copied and `retain` is set.
+pub async fn rename(device: &str, old_id: &str, new_id: &str, retain: bool) -> Result<(), anyhow::Error> {
+	let mut store = VARIABLES.write().await;
+	let Some(profiles) = store.value.profiles.get_mut(device) else { return Ok(()) };
+	let variables = if retain { profiles.get(old_id).cloned() } else { profiles.remove(old_id) };
+	let Some(variables) = variables else { return Ok(()) };
+	let names = variables.keys().cloned().collect::<Vec<_>>();
+	profiles.insert(new_id.to_owned(), variables);
+	store.save()?;
+	drop(store);
+
+	notify_changed(&names.iter().map(String::as_str).collect::<Vec<_>>());
+	Ok(())
+}
+
+/// Tell the frontend to re-render keys whose titles reference any of the given variables.
+fn notify_changed(names: &[&str]) {
+	if let Some(window) = crate::APP_HANDLE.get().and_then(|app| app.get_webview_window("main")) {
+		let _ = window.emit("variables_changed", names);
+	}
+}
+
+fn format_clock(now: &DateTime<Local>, format: &str) -> Option<String> {
+	let items = StrftimeItems::new(format).collect::<Vec<_>>();
+	if items.iter().any(|item| matches!(item, Item::Error)) {
+		return None;
+	}
+	Some(now.format_with_items(items.into_iter()).to_string())
+}
+
+/// Replace the placeholders in a title with the values of the variables they reference, leaving those that cannot be resolved as they are.
+pub async fn resolve(text: &str, device: &str, profile: &str) -> String {
+	if !text.contains("{{") {
+		return text.to_owned();
+	}
+
+	let store = VARIABLES.read().await;
+	let profile_variables = store.value.profiles.get(device).and_then(|profiles| profiles.get(page_root(profile)));
+	let now = Local::now();
+	let lookup = |placeholder: &str| match placeholder.split_once(':') {
+		Some((CLOCK, format)) => format_clock(&now, format),
+		_ if placeholder == CLOCK => format_clock(&now, DEFAULT_CLOCK_FORMAT),
+		_ => profile_variables.and_then(|v| v.get(placeholder)).or_else(|| store.value.global.get(placeholder)).cloned(),
+	};
+
+	let mut resolved = String::with_capacity(text.len());
+	let mut rest = text;
+	while let Some(start) = rest.find("{{") {
+		let Some(length) = rest[start + 2..].find("}}") else { break };
+		let end = start + 2 + length + 2;
+		resolved.push_str(&rest[..start]);
+		match lookup(&rest[start + 2..end - 2]) {
+			Some(value) => resolved.push_str(&value),
+			None => resolved.push_str(&rest[start..end]),
+		}
+		rest = &rest[end..];
+	}
+	resolved.push_str(rest);
+	resolved
+}
+
+/// Save variables if they have changed since they were last saved.
+pub async fn flush() -> Result<(), anyhow::Error> {
+	if STALE.swap(false, Ordering::Relaxed) {
+		VARIABLES.read().await.save()?;
+	}
+	Ok(())
+}
+
+/// Re-render titles that show the time every second.
+pub fn init_variables() {
+	tokio::spawn(async {
+		loop {
+			tokio::time::sleep(Duration::from_secs(1)).await;
+			notify_changed(&[CLOCK]);
+		}
+	});
+}
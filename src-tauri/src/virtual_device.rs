@@ -0,0 +1,305 @@
+//! A software-only device that keeps rendered images in memory and accepts input from a local socket or the frontend.
+
+use crate::events::inbound::{self, PayloadEvent};
+use crate::shared::{DeviceInfo, config_dir};
+use crate::store::{NotProfile, Store};
+
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::RwLock;
+
+/// The layout of a virtual device, as configured by the user.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct VirtualDeviceConfig {
+	pub id: String,
+	pub name: String,
+	pub rows: u8,
+	pub columns: u8,
+	#[serde(default)]
+	pub encoders: u8,
+	#[serde(default)]
+	pub touchpoints: u8,
+	#[serde(default)]
+	pub infobars: u8,
+	#[serde(default)]
+	pub r#type: u8,
+}
+
+impl VirtualDeviceConfig {
+	fn device_id(&self) -> String {
+		format!("vd-{}", self.id)
+	}
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VirtualDevices {
+	/// The loopback port to accept input on, or 0 to disable the socket.
+	pub socket_port: u16,
+	/// The token that connections to the socket must authenticate with, generated when the socket is first enabled.
+	pub socket_token: String,
+	pub devices: Vec<VirtualDeviceConfig>,
+}
+
+impl Default for VirtualDevices {
+	fn default() -> Self {
+		Self {
+			socket_port: 0,
+			socket_token: String::new(),
+			devices: vec![],
+		}
+	}
+}
+
+impl NotProfile for VirtualDevices {}
+
+pub static VIRTUAL_DEVICES: LazyLock<RwLock<Store<VirtualDevices>>> = LazyLock::new(|| RwLock::new(Store::new("virtual_devices", &config_dir(), VirtualDevices::default()).unwrap()));
+
+/// The images most recently rendered to a virtual device, keyed by controller and then by position.
+#[derive(Clone, Default, Serialize)]
+pub struct VirtualDeviceState {
+	pub brightness: u8,
+	pub images: HashMap<String, HashMap<u8, String>>,
+}
+
+static STATES: LazyLock<DashMap<String, VirtualDeviceState>> = LazyLock::new(DashMap::new);
+
+/// The port the input socket is bound to and the task accepting connections on it.
+static SOCKET: LazyLock<Mutex<Option<(u16, tokio::task::JoinHandle<()>)>>> = LazyLock::new(|| Mutex::new(None));
+
+pub fn is_virtual_device(id: &str) -> bool {
+	STATES.contains_key(id)
+}
+
+pub fn get_state(id: &str) -> Option<VirtualDeviceState> {
+	STATES.get(id).map(|state| state.clone())
+}
+
+fn emit_updated(id: &str) {
+	if let Some(window) = crate::APP_HANDLE.get().and_then(|app| app.get_webview_window("main")) {
+		let _ = window.emit("virtual_device_updated", id);
+	}
+}
+
+async fn register(config: &VirtualDeviceConfig) -> Result<(), anyhow::Error> {
+	let id = config.device_id();
+	if STATES.contains_key(&id) {
+		return Ok(());
+	}
+
+	STATES.insert(
+		id.clone(),
+		VirtualDeviceState {
+			brightness: crate::store::profiles::get_device_settings(&id).await.brightness(),
+			images: HashMap::new(),
+		},
+	);
+
+	inbound::devices::register_device(
+		"",
+		PayloadEvent {
+			payload: DeviceInfo {
+				id,
+				plugin: String::new(),
+				name: config.name.clone(),
+				rows: config.rows,
+				columns: config.columns,
+				encoders: config.encoders,
+				touchpoints: config.touchpoints,
+				infobars: config.infobars,
+				r#type: config.r#type,
+			},
+		},
+	)
+	.await
+}
+
+async fn deregister(id: &str) -> Result<(), anyhow::Error> {
+	if !STATES.contains_key(id) {
+		return Ok(());
+	}
+	inbound::devices::deregister_device("", PayloadEvent { payload: id.to_owned() }).await?;
+	STATES.remove(id);
+	Ok(())
+}
+
+/// Register all configured virtual devices and deregister any that are no longer configured.
+pub async fn sync_virtual_devices() -> Result<(), anyhow::Error> {
+	let devices = VIRTUAL_DEVICES.read().await.value.devices.clone();
+
+	let configured = devices.iter().map(|v| v.device_id()).collect::<Vec<_>>();
+	let stale = STATES.iter().map(|v| v.key().clone()).filter(|id| !configured.contains(id)).collect::<Vec<_>>();
+	for id in stale {
+		deregister(&id).await?;
+		crate::variables::forget(&id, None).await?;
+	}
+
+	for device in &devices {
+		register(device).await?;
+	}
+
+	Ok(())
+}
+
+/// Register the configured virtual devices and start the input socket if it is enabled.
+pub fn initialise_virtual_devices() {
+	tokio::spawn(async {
+		if let Err(error) = sync_virtual_devices().await {
+			log::error!("Failed to initialise virtual devices: {error}");
+		}
+		if let Err(error) = update_socket().await {
+			log::error!("Failed to start virtual device socket: {error}");
+		}
+	});
+}
+
+/// Start, stop or rebind the input socket to match the configured port, generating a token for it if it does not have one.
+pub async fn update_socket() -> Result<(), anyhow::Error> {
+	let port = {
+		let mut store = VIRTUAL_DEVICES.write().await;
+		if store.value.socket_port != 0 && store.value.socket_token.is_empty() {
+			store.value.socket_token = crate::http_api::generate_token();
+			store.save()?;
+		}
+		store.value.socket_port
+	};
+
+	let mut socket = SOCKET.lock().unwrap();
+	let wanted = (port != 0).then_some(port);
+	if socket.as_ref().map(|(port, _)| *port) == wanted {
+		return Ok(());
+	}
+
+	if let Some((_, task)) = socket.take() {
+		task.abort();
+	}
+	let Some(port) = wanted else { return Ok(()) };
+
+	let listener = std::net::TcpListener::bind(("127.0.0.1", port))?;
+	listener.set_nonblocking(true)?;
+	let listener = TcpListener::from_std(listener)?;
+	*socket = Some((port, tokio::spawn(accept_connections(listener))));
+	log::info!("Accepting virtual device input on port {port}");
+	Ok(())
+}
+
+pub async fn update_image(context: &crate::shared::Context, image: Option<&str>) -> Result<(), anyhow::Error> {
+	if let Some(mut state) = STATES.get_mut(&context.device) {
+		let images = state.images.entry(context.controller.clone()).or_default();
+		match image {
+			Some(image) => images.insert(context.position, image.to_owned()),
+			None => images.remove(&context.position),
+		};
+	}
+	emit_updated(&context.device);
+	Ok(())
+}
+
+pub async fn clear_screen(id: &str) -> Result<(), anyhow::Error> {
+	if let Some(mut state) = STATES.get_mut(id) {
+		state.images.clear();
+	}
+	emit_updated(id);
+	Ok(())
+}
+
+pub async fn set_brightness(id: &str, brightness: u8) {
+	if let Some(mut state) = STATES.get_mut(id) {
+		state.brightness = brightness.clamp(0, 100);
+	}
+	emit_updated(id);
+}
+
+/// Input that can be sent to a virtual device, using the same shapes as the equivalent inbound events from device plugins.
+#[derive(Deserialize)]
+#[serde(tag = "event")]
+#[serde(rename_all = "camelCase")]
+pub enum VirtualInputEvent {
+	KeyDown(PayloadEvent<inbound::devices::PressPayload>),
+	KeyUp(PayloadEvent<inbound::devices::PressPayload>),
+	EncoderChange(PayloadEvent<inbound::devices::TicksPayload>),
+	EncoderDown(PayloadEvent<inbound::devices::PressPayload>),
+	EncoderUp(PayloadEvent<inbound::devices::PressPayload>),
+	TouchscreenPress(PayloadEvent<inbound::devices::TouchscreenPressPayload>),
+	GetState(PayloadEvent<String>),
+}
+
+/// Process input for a virtual device, returning the device state if it was requested.
+pub async fn process_input(event: VirtualInputEvent) -> Result<Option<VirtualDeviceState>, anyhow::Error> {
+	let device = match &event {
+		VirtualInputEvent::KeyDown(event) | VirtualInputEvent::KeyUp(event) | VirtualInputEvent::EncoderDown(event) | VirtualInputEvent::EncoderUp(event) => &event.payload.device,
+		VirtualInputEvent::EncoderChange(event) => &event.payload.device,
+		VirtualInputEvent::TouchscreenPress(event) => &event.payload.device,
+		VirtualInputEvent::GetState(event) => &event.payload,
+	};
+	if !is_virtual_device(device) {
+		return Err(anyhow::anyhow!("device {device} is not a virtual device"));
+	}
+
+	match event {
+		VirtualInputEvent::KeyDown(event) => inbound::devices::key_down(event).await?,
+		VirtualInputEvent::KeyUp(event) => inbound::devices::key_up(event).await?,
+		VirtualInputEvent::EncoderChange(event) => inbound::devices::encoder_change(event).await?,
+		VirtualInputEvent::EncoderDown(event) => inbound::devices::encoder_down(event).await?,
+		VirtualInputEvent::EncoderUp(event) => inbound::devices::encoder_up(event).await?,
+		VirtualInputEvent::TouchscreenPress(event) => inbound::devices::touchscreen_press(event).await?,
+		VirtualInputEvent::GetState(event) => return Ok(get_state(&event.payload)),
+	}
+
+	Ok(None)
+}
+
+/// Accept newline-delimited JSON input events on a loopback socket.
+async fn accept_connections(listener: TcpListener) {
+	while let Ok((stream, _)) = listener.accept().await {
+		tokio::spawn(accept_connection(stream));
+	}
+}
+
+#[derive(Deserialize)]
+struct Authentication {
+	token: String,
+}
+
+/// Handle a connection to the input socket, which must first send a line of the form `{"token": "..."}` containing the socket token.
+async fn accept_connection(stream: TcpStream) {
+	let (read, mut write) = stream.into_split();
+	let mut lines = BufReader::new(read).lines();
+
+	let Ok(Some(line)) = lines.next_line().await else { return };
+	let expected = VIRTUAL_DEVICES.read().await.value.socket_token.clone();
+	let authenticated = serde_json::from_str::<Authentication>(&line).is_ok_and(|v| !expected.is_empty() && crate::http_api::constant_time_eq(v.token.trim(), &expected));
+	let response = if authenticated {
+		serde_json::json!({ "ok": true })
+	} else {
+		serde_json::json!({ "error": "missing or invalid token" })
+	};
+	if write.write_all(format!("{response}\n").as_bytes()).await.is_err() || !authenticated {
+		return;
+	}
+
+	while let Ok(Some(line)) = lines.next_line().await {
+		if line.trim().is_empty() {
+			continue;
+		}
+
+		let response = match serde_json::from_str::<VirtualInputEvent>(&line) {
+			Ok(event) => match process_input(event).await {
+				Ok(Some(state)) => serde_json::json!({ "state": state }),
+				Ok(None) => serde_json::json!({ "ok": true }),
+				Err(error) => serde_json::json!({ "error": error.to_string() }),
+			},
+			Err(error) => serde_json::json!({ "error": error.to_string() }),
+		};
+
+		if write.write_all(format!("{response}\n").as_bytes()).await.is_err() {
+			break;
+		}
+	}
+}
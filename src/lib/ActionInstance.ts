@@ -1,5 +1,6 @@
 import type { Action } from "./Action.ts";
 import type { ActionState } from "./ActionState.ts";
+import type { Profile } from "./Profile.ts";
 
 export type ActionInstance = {
 	action: Action;
@@ -8,4 +9,5 @@ export type ActionInstance = {
 	current_state: number;
 	settings: any;
 	children: ActionInstance[] | null;
+	page: Profile | null;
 };
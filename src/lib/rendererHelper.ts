@@ -176,7 +176,47 @@ export async function renderImage(
 
 	context.restore();
 
-	if (active && slotContext) setTimeout(async () => await invoke("update_image", { context: slotContext, image: canvas.toDataURL("image/jpeg") }), 10);
+	if (active && slotContext) {
+		// Animated images are sent to Elgato devices unchanged if nothing has been drawn over them, as drawing them to the canvas keeps only the first frame.
+		const unmodified =
+			processImage &&
+			slotContext.device.startsWith("sd-") &&
+			slotContext.controller == "Keypad" &&
+			!state.show &&
+			state.background_colour.startsWith("#000000") &&
+			(state.image_scale || 100) == 100 &&
+			!showOk &&
+			!showAlert &&
+			!pressed &&
+			!rotation;
+		const animated = unmodified ? await getAnimatedImage(getImage(state.image, fallback)) : null;
+		setTimeout(async () => await invoke("update_image", { context: slotContext, image: animated ?? canvas.toDataURL("image/jpeg") }), 10);
+	}
+}
+
+/** Fetch an image as a data URL if it is an animated GIF, APNG or WebP. */
+async function getAnimatedImage(source: string): Promise<string | null> {
+	try {
+		const blob = await (await fetch(source)).blob();
+		const bytes = new Uint8Array(await blob.slice(0, 4096).arrayBuffer());
+		const text = String.fromCharCode(...bytes);
+		const actl = text.indexOf("acTL");
+		const idat = text.indexOf("IDAT");
+		const animated =
+			text.startsWith("GIF8") ||
+			(text.slice(1, 4) == "PNG" && actl != -1 && (idat == -1 || actl < idat)) ||
+			(text.startsWith("RIFF") && text.slice(8, 12) == "WEBP" && text.includes("ANIM"));
+		if (!animated) return null;
+
+		return await new Promise((resolve, reject) => {
+			const reader = new FileReader();
+			reader.onload = () => resolve(reader.result as string);
+			reader.onerror = reject;
+			reader.readAsDataURL(blob);
+		});
+	} catch {
+		return null;
+	}
 }
 
 export async function resizeImage(source: string): Promise<string | undefined> {
@@ -12,6 +12,26 @@ export type Settings = {
 	separatewine: boolean;
 	developer: boolean;
 	disableelgato: boolean;
+	plugin_restart_limit: number;
+	loopback_only: boolean;
+	require_plugin_tokens: boolean;
+	api_enabled: boolean;
+	api_port: number;
+	api_token: string;
+	mqtt_enabled: boolean;
+	mqtt_host: string;
+	mqtt_port: number;
+	mqtt_username: string;
+	mqtt_password: string;
+	mqtt_topic_prefix: string;
+	trusted_plugin_keys: string[];
+};
+
+/** Settings for a single device, each of which overrides the global setting when not null. */
+export type DeviceSettings = {
+	brightness: number | null;
+	rotation: number | null;
+	sleep_timeout_minutes: number | null;
 };
 
 import { invoke } from "@tauri-apps/api/core";
@@ -21,6 +41,17 @@ import { locale } from "./i18n.ts";
 export const settings: Writable<Settings | null> = writable(null);
 (async () => settings.set(await invoke("get_settings")))();
 export const localisations: Writable<{ [plugin: string]: any } | null> = writable(null);
+
+export const deviceSettings: Writable<{ [device: string]: DeviceSettings }> = writable({});
+export async function loadDeviceSettings(device: string) {
+	const value: DeviceSettings = await invoke("get_device_settings", { device });
+	deviceSettings.update((all) => ({ ...all, [device]: value }));
+}
+export async function setDeviceSettings(device: string, value: DeviceSettings) {
+	await invoke("set_device_settings", { device, settings: value });
+	deviceSettings.update((all) => ({ ...all, [device]: value }));
+}
+
 settings.subscribe(async (value) => {
 	if (value) {
 		await invoke("set_settings", { settings: value });